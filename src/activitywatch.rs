@@ -0,0 +1,199 @@
+//! Conversion from and to the JSON bucket export format of ActivityWatch.
+//!
+//! An export looks like `{"buckets": {"<id>": {"type": "currentwindow",
+//! "hostname": ..., "events": [{"timestamp": ..., "duration": ..., "data":
+//! {"app": ..., "title": ...}}]}}}`. Window events map to samples taken every
+//! `SAMPLE_INTERVAL` seconds, `afkstatus` events map to the idle flag.
+
+use chrono::prelude::*;
+use crate::db::{Sample, SAMPLE_INTERVAL};
+use crate::spans::Span;
+
+const WINDOW_TYPE: &str = "currentwindow";
+const AFK_TYPE: &str = "afkstatus";
+
+#[derive(Debug)]
+struct Event {
+    host: String,
+    start: i64,
+    end: i64,
+    data: json::JsonValue,
+}
+
+fn events(bucket: &json::JsonValue) -> Result<Vec<Event>, String> {
+    let host = bucket["hostname"].as_str().unwrap_or_default();
+    let mut events = vec![];
+    for event in bucket["events"].members() {
+        let timestamp = event["timestamp"].as_str().ok_or("Event without timestamp")?;
+        let start = DateTime::parse_from_rfc3339(timestamp).map_err(|e| format!("Invalid timestamp {}: {}", timestamp, e))?;
+        let duration = event["duration"].as_f64().unwrap_or(0.0);
+        events.push(Event {
+            host: host.to_string(),
+            start: start.timestamp(),
+            end: start.timestamp() + duration.round() as i64,
+            data: event["data"].clone(),
+        });
+    }
+    events.sort_by_key(|e| e.start);
+    Ok(events)
+}
+
+fn buckets(export: &json::JsonValue) -> Vec<&json::JsonValue> {
+    // exports of the whole server wrap the buckets, exports of single buckets may not
+    if export["buckets"].is_object() {
+        export["buckets"].entries().map(|(_, b)| b).collect()
+    } else if export["events"].is_array() {
+        vec![export]
+    } else {
+        export.entries().map(|(_, b)| b).filter(|b| b["events"].is_array()).collect()
+    }
+}
+
+/// Converts all window events of an export into samples of the bucket's host,
+/// samples that fall into a period the host reported as afk are marked as idle.
+pub fn samples(export: &json::JsonValue) -> Result<Vec<Sample>, String> {
+    let mut windows = vec![];
+    let mut afk = vec![];
+    for bucket in buckets(export) {
+        match bucket["type"].as_str() {
+            Some(WINDOW_TYPE) => windows.extend(events(bucket)?),
+            Some(AFK_TYPE) => afk.extend(events(bucket)?.into_iter().filter(|e| e.data["status"] == "afk")),
+            _ => {},
+        }
+    }
+    if windows.is_empty() {
+        return Err(format!("No events in buckets of type {}", WINDOW_TYPE));
+    }
+
+    let mut samples = vec![];
+    for event in windows {
+        // round to the closest number of samples so short events aren't lost entirely
+        let count = (event.end - event.start + SAMPLE_INTERVAL / 2) / SAMPLE_INTERVAL;
+        for i in 0..count {
+            let ts = event.start + i * SAMPLE_INTERVAL;
+            samples.push(Sample {
+                class: event.data["app"].as_str().unwrap_or("unknown").to_string(),
+                title: event.data["title"].as_str().unwrap_or_default().to_string(),
+                idle: afk.iter().any(|a| a.host == event.host && a.start <= ts && ts < a.end),
+                ts,
+                host: event.host.clone(),
+            });
+        }
+    }
+    Ok(samples)
+}
+
+fn event(start: i64, end: i64, data: json::JsonValue) -> json::JsonValue {
+    json::object!{
+        timestamp: Utc.timestamp_opt(start, 0).unwrap().to_rfc3339(),
+        duration: (end - start) as f64,
+        data: data,
+    }
+}
+
+fn bucket(id: &str, kind: &str, client: &str, hostname: &str, events: Vec<json::JsonValue>) -> json::JsonValue {
+    json::object!{
+        id: id,
+        created: Utc::now().to_rfc3339(),
+        name: json::Null,
+        type: kind,
+        client: client,
+        hostname: hostname,
+        events: events,
+    }
+}
+
+/// Builds an export with a window bucket made of `spans` and an afk bucket
+/// telling when they were idle for each host, which ActivityWatch can import
+/// as is. Spans without host are attributed to `default_host`.
+pub fn export(spans: &[Span], default_host: &str) -> json::JsonValue {
+    let mut hosts: Vec<&str> = spans.iter().map(|s| s.host.as_str()).collect();
+    hosts.sort();
+    hosts.dedup();
+
+    let mut buckets = json::JsonValue::new_object();
+    for host in hosts {
+        let spans: Vec<&Span> = spans.iter().filter(|s| s.host == host).collect();
+        let hostname = if host.is_empty() { default_host } else { host };
+
+        let windows = spans.iter().map(|s| event(s.start, s.end, json::object!{
+            app: s.class.clone(),
+            title: s.title.clone(),
+        })).collect();
+
+        let mut afk: Vec<(i64, i64, bool)> = vec![];
+        for s in &spans {
+            match afk.last_mut() {
+                Some(last) if last.2 == s.idle && last.1 >= s.start => last.1 = s.end,
+                _ => afk.push((s.start, s.end, s.idle)),
+            }
+        }
+        let afk = afk.into_iter().map(|(start, end, idle)| event(start, end, json::object!{
+            status: if idle { "afk" } else { "not-afk" },
+        })).collect();
+
+        let window_id = format!("aw-watcher-window_{}", hostname);
+        let afk_id = format!("aw-watcher-afk_{}", hostname);
+        buckets[window_id.as_str()] = bucket(&window_id, WINDOW_TYPE, "aw-watcher-window", hostname, windows);
+        buckets[afk_id.as_str()] = bucket(&afk_id, AFK_TYPE, "aw-watcher-afk", hostname, afk);
+    }
+    json::object!{ buckets: buckets }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(host: &str, class: &str, idle: bool, start: i64, end: i64) -> Span {
+        Span { class: class.to_string(), title: format!("{} title", class), idle, host: host.to_string(), start, end }
+    }
+
+    #[test]
+    fn export_round_trips_to_samples() {
+        let spans = vec![
+            span("", "vim", false, 0, 30),
+            span("", "vim", true, 30, 50),
+            span("b", "firefox", false, 10, 30),
+        ];
+        let export = export(&spans, "a");
+        assert!(export["buckets"]["aw-watcher-window_a"].is_object());
+        assert!(export["buckets"]["aw-watcher-afk_b"].is_object());
+
+        let mut samples = samples(&export).unwrap();
+        samples.sort_by(|a, b| (&a.host, a.ts).cmp(&(&b.host, b.ts)));
+        let result: Vec<(&str, &str, bool, i64)> = samples.iter().map(|s| (s.host.as_str(), s.class.as_str(), s.idle, s.ts)).collect();
+        assert_eq!(result, vec![
+            ("a", "vim", false, 0),
+            ("a", "vim", false, 10),
+            ("a", "vim", false, 20),
+            ("a", "vim", true, 30),
+            ("a", "vim", true, 40),
+            ("b", "firefox", false, 10),
+            ("b", "firefox", false, 20),
+        ]);
+        assert_eq!(samples[0].title, "vim title");
+    }
+
+    #[test]
+    fn short_events_round_to_closest_sample_count() {
+        let bucket = json::object!{
+            type: WINDOW_TYPE,
+            hostname: "a",
+            events: [
+                event(0, 4, json::object!{ app: "dropped" }),
+                event(100, 105, json::object!{ app: "kept" }),
+                event(200, 214, json::object!{ app: "once" }),
+            ],
+        };
+        let samples = samples(&bucket).unwrap();
+        let result: Vec<(&str, i64)> = samples.iter().map(|s| (s.class.as_str(), s.ts)).collect();
+        assert_eq!(result, vec![("kept", 100), ("once", 200)]);
+    }
+
+    #[test]
+    fn rejects_exports_without_windows() {
+        assert!(samples(&json::object!{ buckets: {} }).is_err());
+        let invalid = json::object!{ type: WINDOW_TYPE, events: [{ timestamp: "yesterday", duration: 10.0 }] };
+        assert!(samples(&invalid).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use rusqlite::Connection;
+use crate::db::Sample;
+
+/// A tag and note attached to the time `start <= t < end`, regardless of the
+/// windows used during it, e.g. "sprint planning" or "incident #123".
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub id: i64,
+    pub start: i64,
+    pub end: i64,
+    pub tag: String,
+    pub note: String,
+}
+
+impl Annotation {
+    pub fn contains(&self, ts: i64) -> bool {
+        ts >= self.start && ts < self.end
+    }
+}
+
+pub fn insert(conn: &Connection, annotation: &Annotation) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO annotations (start, end, tag, note) values (?1, ?2, ?3, ?4);",
+        (annotation.start, annotation.end, &annotation.tag, &annotation.note),
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Removes an annotation, returns whether it existed.
+pub fn remove(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    Ok(conn.execute("delete from annotations where id = ?1;", [id])? > 0)
+}
+
+/// Annotations overlapping `start <= t < end` ordered by their start.
+pub fn load(conn: &Connection, start: i64, end: i64) -> rusqlite::Result<Vec<Annotation>> {
+    let mut stmt = conn.prepare("select id, start, end, tag, note from annotations where end > ?1 and start < ?2 order by start asc, id asc;")?;
+    let rows = stmt.query_map((start, end), |row| {
+        Ok(Annotation {
+            id: row.get(0)?,
+            start: row.get(1)?,
+            end: row.get(2)?,
+            tag: row.get(3)?,
+            note: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// The tag of the first annotation containing `ts`.
+pub fn tag_at(annotations: &[Annotation], ts: i64) -> Option<&str> {
+    annotations.iter().find(|a| a.contains(ts)).map(|a| a.tag.as_str())
+}
+
+/// Only keeps the samples within an annotation tagged `tag`.
+pub fn tagged(samples: Vec<Sample>, annotations: &[Annotation], tag: &str) -> Vec<Sample> {
+    samples.into_iter().filter(|s| annotations.iter().any(|a| a.tag == tag && a.contains(s.ts))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    fn annotation(start: i64, end: i64, tag: &str) -> Annotation {
+        Annotation { id: 0, start, end, tag: tag.to_string(), note: String::new() }
+    }
+
+    fn times(samples: &[Sample]) -> Vec<i64> {
+        samples.iter().map(|s| s.ts).collect()
+    }
+
+    fn samples() -> Vec<Sample> {
+        (0..10).map(|i| db::sample("a", "vim", i * 10)).collect()
+    }
+
+    #[test]
+    fn overlapping_tags_keep_samples_once() {
+        let annotations = vec![annotation(0, 40, "review"), annotation(20, 60, "review"), annotation(30, 100, "lunch")];
+        assert_eq!(times(&tagged(samples(), &annotations, "review")), vec![0, 10, 20, 30, 40, 50]);
+        assert_eq!(times(&tagged(samples(), &annotations, "lunch")), vec![30, 40, 50, 60, 70, 80, 90]);
+        assert_eq!(tag_at(&annotations, 30), Some("review"));
+    }
+
+    #[test]
+    fn samples_count_by_their_time() {
+        // a range starting or ending within the interval of a sample only
+        // keeps it if it contains the time the sample was recorded at
+        let annotations = vec![annotation(15, 45, "review")];
+        assert_eq!(times(&tagged(samples(), &annotations, "review")), vec![20, 30, 40]);
+    }
+
+    #[test]
+    fn other_or_missing_tags_keep_nothing() {
+        let annotations = vec![annotation(0, 100, "lunch")];
+        assert!(tagged(samples(), &annotations, "review").is_empty());
+        assert!(tagged(samples(), &[], "review").is_empty());
+        assert!(tagged(vec![], &annotations, "lunch").is_empty());
+        assert_eq!(tag_at(&annotations, 100), None);
+    }
+}
@@ -0,0 +1,189 @@
+use chrono::prelude::*;
+use chrono::Days;
+use clap::{Parser, ValueEnum};
+use std::collections::HashMap;
+use std::process;
+use tracking::config::{self, Config};
+use tracking::day::Day;
+use tracking::db;
+use tracking::edits;
+use tracking::spans::{self, Span};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Resolution {
+    /// merge consecutive samples of the same window into spans
+    Span,
+    /// only keep the time per class for every hour
+    Hour,
+    /// only keep the time per class for every day
+    Day,
+}
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to compact, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(short, long, help = "The config file, defaults to ~/.config/tracking/config.json can also be set with TRACKING_CONFIG environment variable")]
+    config: Option<String>,
+
+    #[arg(short, long, help = "Number of days to keep at full resolution, defaults to retention.keep_days of the config or 90")]
+    keep_days: Option<u64>,
+
+    #[arg(short, long, help = "What to keep of older samples, defaults to retention.resolution of the config or span", value_enum)]
+    resolution: Option<Resolution>,
+}
+
+
+/// Sums up the time of `spans` per host, class and idle state within buckets
+/// of `length` seconds starting at `origin` and lays the sums out one after
+/// another from the start of each bucket. Titles are dropped.
+fn aggregate(spans: &[Span], origin: i64, length: i64) -> Vec<Span> {
+    let bucket_start = |ts: i64| origin + (ts - origin).div_euclid(length) * length;
+    let mut sums: HashMap<(i64, String, String, bool), i64> = HashMap::new();
+    for s in spans {
+        // split spans crossing into the next bucket
+        let mut start = s.start;
+        while start < s.end {
+            let end = s.end.min(bucket_start(start) + length);
+            *sums.entry((bucket_start(start), s.host.clone(), s.class.clone(), s.idle)).or_default() += end - start;
+            start = end;
+        }
+    }
+    let mut sums: Vec<((i64, String, String, bool), i64)> = sums.into_iter().collect();
+    sums.sort_by(|a, b| (a.0.0, &a.0.1).cmp(&(b.0.0, &b.0.1)).then(b.1.cmp(&a.1)).then(a.0.2.cmp(&b.0.2)));
+
+    let mut aggregated: Vec<Span> = vec![];
+    for ((bucket, host, class, idle), seconds) in sums {
+        let start = match aggregated.last() {
+            Some(last) if last.host == host && bucket_start(last.start) == bucket => last.end,
+            _ => bucket,
+        };
+        aggregated.push(Span { class, title: "".to_string(), idle, host, start, end: start + seconds });
+    }
+    aggregated
+}
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+    let config = Config::load(&config::path(args.config)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    let keep_days = args.keep_days.unwrap_or(config.retention.keep_days);
+    if keep_days < 1 {
+        eprintln!("At least the current day must be kept at full resolution");
+        process::exit(1);
+    }
+    let resolution = args.resolution.unwrap_or_else(|| {
+        Resolution::from_str(&config.retention.resolution, true).unwrap_or_else(|e| {
+            eprintln!("Invalid retention.resolution: {}", e);
+            process::exit(1);
+        })
+    });
+
+    let mut conn = db::open(&db).unwrap();
+    let cutoff = Local::now().date_naive().checked_sub_days(Days::new(keep_days)).unwrap();
+    let oldest: Option<i64> = conn.query_row("select min(ts) from tracking;", (), |row| row.get(0)).unwrap();
+    let Some(oldest) = oldest else {
+        println!("Nothing to compact");
+        return;
+    };
+
+    let mut date = Local.timestamp_opt(oldest, 0).unwrap().date_naive();
+    let mut compacted = 0;
+    let mut spans_written = 0;
+    // one day at a time to keep memory in check with years of samples
+    while date < cutoff {
+        let day = Day::new(&Local, date);
+        let filter = db::Filter {
+            start: day.start.timestamp(),
+            end: day.end.timestamp(),
+            idle: true,
+            host: None,
+        };
+        let samples = db::raw_samples(&conn, &filter).unwrap();
+        date = date.succ_opt().unwrap();
+        if samples.is_empty() {
+            continue;
+        }
+        // hours and days are laid out from their start, edits would hit the
+        // wrong time afterwards, so they are applied for good before
+        let edits = if resolution == Resolution::Span { vec![] } else { edits::load(&conn, filter.start, filter.end, &None).unwrap() };
+        let count = samples.len();
+        let samples = if edits.is_empty() { samples } else { edits::apply(samples, &edits, &filter) };
+
+        let merged = spans::merge(&samples);
+        let merged = match resolution {
+            Resolution::Span => merged,
+            Resolution::Hour => aggregate(&merged, filter.start, 3600),
+            Resolution::Day => aggregate(&merged, filter.start, day.seconds()),
+        };
+
+        let tx = conn.transaction().unwrap();
+        for span in &merged {
+            db::insert_compacted(&tx, span).unwrap();
+        }
+        tx.execute("delete from tracking where ts >= ?1 and ts < ?2;", (filter.start, filter.end)).unwrap();
+        for edit in &edits {
+            edits::cut(&tx, edit, filter.start, filter.end).unwrap();
+        }
+        tx.commit().unwrap();
+        compacted += count;
+        spans_written += merged.len();
+    }
+
+    println!("Compacted {} samples before {} into {} spans", compacted, cutoff, spans_written);
+    if compacted > 0 {
+        conn.execute("vacuum;", ()).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(host: &str, class: &str, start: i64, end: i64) -> Span {
+        Span { class: class.to_string(), title: "title".to_string(), idle: false, host: host.to_string(), start, end }
+    }
+
+    fn times(spans: &[Span]) -> Vec<(&str, &str, i64, i64)> {
+        spans.iter().map(|s| (s.host.as_str(), s.class.as_str(), s.start, s.end)).collect()
+    }
+
+    #[test]
+    fn sums_up_classes_per_bucket() {
+        let spans = vec![span("a", "vim", 0, 600), span("a", "firefox", 600, 900), span("a", "vim", 1200, 1500)];
+        let aggregated = aggregate(&spans, 0, 3600);
+        // the longest class first, titles are dropped
+        assert_eq!(times(&aggregated), vec![("a", "vim", 0, 900), ("a", "firefox", 900, 1200)]);
+        assert!(aggregated.iter().all(|s| s.title.is_empty()));
+    }
+
+    #[test]
+    fn splits_spans_crossing_buckets() {
+        let aggregated = aggregate(&[span("a", "vim", 3000, 4200)], 0, 3600);
+        assert_eq!(times(&aggregated), vec![("a", "vim", 0, 600), ("a", "vim", 3600, 4200)]);
+    }
+
+    #[test]
+    fn keeps_hosts_and_idle_apart() {
+        let mut idle = span("a", "vim", 300, 500);
+        idle.idle = true;
+        let spans = vec![span("a", "vim", 0, 300), idle, span("b", "vim", 0, 1200)];
+        let aggregated = aggregate(&spans, 0, 3600);
+        assert_eq!(times(&aggregated), vec![("a", "vim", 0, 300), ("a", "vim", 300, 500), ("b", "vim", 0, 1200)]);
+        assert_eq!(aggregated.iter().map(|s| s.idle).collect::<Vec<_>>(), vec![false, true, false]);
+    }
+
+    #[test]
+    fn buckets_start_at_origin() {
+        let aggregated = aggregate(&[span("a", "vim", 50, 250)], 100, 3600);
+        assert_eq!(times(&aggregated), vec![("a", "vim", -3500, -3450), ("a", "vim", 100, 250)]);
+    }
+}
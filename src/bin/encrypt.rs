@@ -0,0 +1,54 @@
+use rusqlite::{Connection, OpenFlags};
+use clap::{Parser, ArgAction};
+use std::path::Path;
+use std::process;
+use tracking::db;
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to convert, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(long, help = "Write an unencrypted copy of an encrypted database instead", action = ArgAction::SetTrue)]
+    decrypt: Option<bool>,
+
+    #[arg(help = "The file to write the converted copy to")]
+    output: String,
+}
+
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+    let decrypt = args.decrypt.is_some() && args.decrypt.unwrap();
+    if Path::new(&args.output).exists() {
+        eprintln!("{} already exists", args.output);
+        process::exit(1);
+    }
+    let Some(key) = db::key(true).unwrap() else {
+        eprintln!("No key set, use TRACKING_KEY, TRACKING_KEY_FILE or the keyring");
+        process::exit(1);
+    };
+
+    let conn = if decrypt {
+        db::connect(&db, OpenFlags::default())
+    } else {
+        Connection::open(&db)
+    }.unwrap_or_else(|e| {
+        eprintln!("Could not open {}: {}", db, e);
+        process::exit(1);
+    });
+    // an empty key attaches the copy without encryption
+    let target_key = if decrypt { "" } else { key.as_str() };
+    conn.execute("attach database ?1 as converted key ?2;", (&args.output, target_key)).unwrap();
+    conn.query_row("select sqlcipher_export('converted');", (), |_| Ok(())).unwrap_or_else(|e| {
+        eprintln!("Could not convert {}: {}", db, e);
+        process::exit(1);
+    });
+    let version: i64 = conn.query_row("pragma main.user_version;", (), |row| row.get(0)).unwrap();
+    conn.pragma_update(Some(rusqlite::DatabaseName::Attached("converted")), "user_version", version).unwrap();
+    conn.execute("detach database converted;", ()).unwrap();
+    println!("Wrote {} copy of {} to {}", if decrypt { "a decrypted" } else { "an encrypted" }, db, args.output);
+}
@@ -0,0 +1,136 @@
+use chrono::prelude::*;
+use clap::{Parser, ValueEnum, ArgAction};
+use std::fs;
+use std::process;
+use tracking::activitywatch;
+use tracking::day::{parse_date, Day};
+use tracking::db;
+use tracking::spans::{self, Span};
+use tracking::table::Table;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Csv,
+    Jsonl,
+    Json,
+    Activitywatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Field {
+    Class,
+    Title,
+}
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to connect to, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(short, long, help = "The start date in the format YYYY-MM-DD, defaults to today")]
+    start: Option<String>,
+
+    #[arg(short, long, help = "The end date in the format YYYY-MM-DD, defaults to today")]
+    end: Option<String>,
+
+    #[arg(long, help = "Only export samples recorded on this host")]
+    host: Option<String>,
+
+    #[arg(short, long, help = "Output format", value_enum, default_value_t = Format::Csv)]
+    format: Format,
+
+    #[arg(long, help = "Export merged focus spans instead of raw samples", action = ArgAction::SetTrue)]
+    spans: Option<bool>,
+
+    #[arg(short, long, help = "Include idle samples in export", action = ArgAction::SetTrue)]
+    idle: Option<bool>,
+
+    #[arg(short, long, help = "Replace the contents of this field with 'redacted', can be given multiple times", value_enum, action = ArgAction::Append)]
+    redact: Vec<Field>,
+
+    #[arg(short, long, help = "Write to this file instead of stdout")]
+    output: Option<String>,
+}
+
+
+const REDACTED: &str = "redacted";
+
+fn time(ts: i64) -> String {
+    Local.timestamp_opt(ts, 0).unwrap().to_rfc3339()
+}
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+
+    let today = Local::now().date_naive();
+    let start = args.start.map(|s| parse_date(&s).expect("Invalid start date")).unwrap_or(today);
+    let end = args.end.map(|e| parse_date(&e).expect("Invalid end date")).unwrap_or(today);
+    let filter = db::Filter {
+        start: Day::new(&Local, start).start.timestamp(),
+        end: Day::new(&Local, end).end.timestamp(),
+        idle: args.idle.is_some() && args.idle.unwrap(),
+        host: args.host,
+    };
+
+    let conn = db::open_read_only(&db).unwrap_or_else(|e| {
+        eprintln!("Could not open {}: {}", db, e);
+        process::exit(1);
+    });
+    let mut samples = db::samples(&conn, &filter).unwrap();
+    for s in samples.iter_mut() {
+        if args.redact.contains(&Field::Class) {
+            s.class = REDACTED.to_string();
+        }
+        if args.redact.contains(&Field::Title) {
+            s.title = REDACTED.to_string();
+        }
+    }
+
+    let (header, rows): (Vec<&str>, Vec<json::JsonValue>) = if args.spans.is_some() && args.spans.unwrap() {
+        let rows = spans::merge(&samples).iter().map(|s: &Span| json::object!{
+            start: s.start,
+            end: s.end,
+            start_time: time(s.start),
+            end_time: time(s.end),
+            duration: s.seconds(),
+            class: s.class.clone(),
+            title: s.title.clone(),
+            idle: s.idle,
+            host: s.host.clone(),
+        }).collect();
+        (vec!["start", "end", "start_time", "end_time", "duration", "class", "title", "idle", "host"], rows)
+    } else {
+        let rows = samples.iter().map(|s| json::object!{
+            ts: s.ts,
+            time: time(s.ts),
+            class: s.class.clone(),
+            title: s.title.clone(),
+            idle: s.idle,
+            host: s.host.clone(),
+        }).collect();
+        (vec!["ts", "time", "class", "title", "idle", "host"], rows)
+    };
+
+    let output = match args.format {
+        Format::Csv => {
+            let mut table = Table::new(&header);
+            for row in &rows {
+                table.push(header.iter().map(|h| row[*h].to_string()).collect());
+            }
+            table.render_csv()
+        },
+        Format::Jsonl => rows.iter().map(|r| r.dump() + "\n").collect(),
+        Format::Json => json::JsonValue::Array(rows).pretty(2) + "\n",
+        Format::Activitywatch => activitywatch::export(&spans::merge(&samples), &db::hostname()).pretty(2) + "\n",
+    };
+
+    if let Some(path) = args.output {
+        fs::write(&path, output).unwrap_or_else(|e| panic!("Could not write {}: {}", path, e));
+    } else {
+        print!("{}", output);
+    }
+}
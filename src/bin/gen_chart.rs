@@ -1,54 +1,163 @@
 use num_traits::cast::FromPrimitive;
-use rusqlite::Connection;
 use chrono::prelude::*;
-use chrono::naive::Days;
-use chrono::{Datelike, Local, Duration};
+use chrono::{Datelike, Local, Duration, Months};
+use chrono_tz::Tz;
 use std::collections::HashMap;
-use std::env;
+use std::fmt::Display;
+use std::process;
+use std::sync::OnceLock;
 use rand::{thread_rng, Rng};
-use clap::{Parser, ArgAction};
+use clap::{Parser, ValueEnum, ArgAction};
 use plotters::prelude::*;
 use plotters::backend::BitMapBackend;
-use lazy_static::lazy_static;
+use font_kit::source::SystemSource;
+use regex::Regex;
+use tracking::annotations::{self, Annotation};
+use tracking::config::{self, Config};
+use tracking::day::{self, parse_date, Day};
+use tracking::db;
+use tracking::runs;
+use tracking::spans::{self, Span, MAX_GAP};
+use tracking::switches::{self, Analysis};
+use tracking::theme::{Rgb, Theme};
 
-const BACKGROUND: RGBColor = RGBColor(128, 128, 128);
 const TIME_MARGIN: i32 = 50;
 const DATE_MARGIN: i32 = 30;
-const TEXT_BLOCK_SIZE: i32 = 24;
 const TEXT_MARGIN: i32 = 4;
-const DAILY_TIME_MARGIN: i32 = 6 * TEXT_BLOCK_SIZE;
 const DAY_MARGIN: i32 = 5;
 const DAY_WIDTH: i32 = 140;
 const BAR_MARGIN: i32 = 20;
-const BAR_WIDTH: i32 = DAY_WIDTH - BAR_MARGIN * 2;
 const LEGEND_MARGIN: i32 = 5;
+const TAG_FONT_SIZE: i32 = 12;
+/// Distance of the lines hatching times without data.
+const HATCH_STEP: i32 = 8;
+/// Hours with this many context switches fill the whole margin.
+const MAX_SWITCHES: usize = 12;
+/// Deep work blocks shorter than this are not marked.
+const MIN_DEEP_WORK: i64 = 30 * 60;
+const HEATMAP_FONT_SIZE: i32 = 12;
+const CALENDAR_CELL: i32 = 14;
+const STACK_WIDTH: i32 = 40;
+const STACK_MARGIN: i32 = 4;
+const PIE_SIZE: i32 = 400;
+const ROW_LABEL_WIDTH: i32 = 150;
+const ROW_TOTAL_WIDTH: i32 = 100;
+/// Number of titles listed with `--class` unless `--top` is given.
+const TOP_TITLES: usize = 10;
+/// Name of the group of titles that aren't among the top ones or don't match
+/// `--title-pattern`.
+const OTHER_TITLES: &str = "other titles";
+/// Days with this many active hours get the full color in the calendar.
+const CALENDAR_FULL_HOURS: i64 = 8;
+
+
+/// The default font, a sans serif font is used if it isn't installed.
+const FONT: &str = "hack";
+/// Size of regular text the sizes of other text are given for.
+const FONT_SIZE: i32 = 16;
+
+/// Colors and font of the chart, set from the config before anything is drawn.
+struct Style {
+    background: RGBColor,
+    grid: RGBColor,
+    text: RGBColor,
+    font: String,
+    font_size: i32,
+    colors: Vec<RGBColor>,
+    heatmap_empty: RGBColor,
+    heatmap: RGBColor,
+    transition: RGBColor,
+    switches: RGBColor,
+    deep_work: RGBColor,
+    tags: RGBColor,
+}
 
+static STYLE: OnceLock<Style> = OnceLock::new();
 
-lazy_static! {
-    static ref COLORS: Vec<RGBColor> = vec![RGBColor(255, 0, 0), RGBColor(0, 255, 0), RGBColor(0, 0, 255), RGBColor(255, 255, 0), RGBColor(255, 0, 255), RGBColor(0, 255, 255), RGBColor(255, 255, 255), RGBColor(0, 0, 0), RGBColor(85, 85, 85), RGBColor(170, 170, 170), RGBColor(128, 255, 0), RGBColor(128, 0, 255), RGBColor(255, 128, 0)];
+fn theme() -> &'static Style {
+    STYLE.get().expect("style must be set before drawing")
 }
 
+/// Text of `size`, which is scaled along with the configured font size.
+fn font(size: i32) -> TextStyle<'static> {
+    let theme = theme();
+    (theme.font.as_str(), (size * theme.font_size / FONT_SIZE) as f32).into_font().color(&theme.text)
+}
 
-#[derive(Debug, Clone)]
-struct Row {
-    class: String,
-    ts: i64,
+/// Height of a line of regular text.
+fn text_block() -> i32 {
+    theme().font_size + 2 * TEXT_MARGIN
+}
+
+/// `px` at the default font size grown along with a larger configured one,
+/// for margins and columns that hold text.
+fn scaled(px: i32) -> i32 {
+    (px * theme().font_size / FONT_SIZE).max(px)
+}
+
+fn bar_width() -> i32 {
+    scaled(DAY_WIDTH) - BAR_MARGIN * 2
+}
+
+/// Resolves the font of `theme`, a configured font that isn't installed is an
+/// error while the default one falls back to a sans serif font.
+fn set_style(theme: &Theme) -> Result<(), String> {
+    let installed = |family: &str| matches!(family, "serif" | "sans-serif" | "monospace") || SystemSource::new().select_family_by_name(family).is_ok();
+    let font = match &theme.font {
+        Some(font) if installed(font) => font.clone(),
+        Some(font) => return Err(format!("Font {} is not installed, install it or set another chart.font in the config", font)),
+        None if installed(FONT) => FONT.to_string(),
+        None => {
+            eprintln!("Font {} is not installed, using sans-serif instead, set chart.font in the config to use another one", FONT);
+            "sans-serif".to_string()
+        },
+    };
+    let color = |(r, g, b): Rgb| RGBColor(r, g, b);
+    let style = Style {
+        background: color(theme.background),
+        grid: color(theme.grid),
+        text: color(theme.text),
+        font,
+        font_size: theme.font_size as i32,
+        colors: theme.palette.iter().copied().map(color).collect(),
+        heatmap_empty: color(theme.heatmap_empty),
+        heatmap: color(theme.heatmap),
+        transition: color(theme.transition),
+        switches: color(theme.switches),
+        deep_work: color(theme.deep_work),
+        tags: color(theme.tags),
+    };
+    STYLE.set(style).map_err(|_| "The style was already set".to_string())
 }
 
+
 #[derive(Debug)]
 struct TaskClass {
     class: String,
-    count: i64,
+    secs: i64,
     color: RGBColor
 }
 
 
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Group {
+    Class,
+    Category,
+}
+
+/// Seconds of activity per weekday, starting with Monday, and hour of the day.
+type Grid = [[i64; 24]; 7];
+
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     #[arg(short, long, help = "The database to connect to, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
     database: Option<String>,
 
+    #[arg(short, long, help = "The config file, defaults to ~/.config/tracking/config.json can also be set with TRACKING_CONFIG environment variable")]
+    config: Option<String>,
+
     #[arg(short, long, help = "The start date in the format YYYY-MM-DD, defaults to today")]
     start: Option<String>,
 
@@ -68,7 +177,58 @@ struct Args {
     idle: Option<bool>,
 
     #[arg(long, help = "Height of the 24 hour portion of the graph, defaults to 500 px", default_value_t = 500)]
-    height: i32
+    height: i32,
+
+    #[arg(long, help = "Draw the days as rows with the time on the horizontal axis", action = ArgAction::SetTrue)]
+    horizontal: Option<bool>,
+
+    #[arg(long, help = "Height of a day in the horizontal layout, defaults to the height of a line of text")]
+    row_height: Option<i32>,
+
+    #[arg(long, help = "Width of the 24 hour portion of the horizontal layout, defaults to 960 px", default_value_t = 960)]
+    width: i32,
+
+    #[arg(long, help = "Only include samples recorded on this host, all hosts are included by default")]
+    host: Option<String>,
+
+    #[arg(long, help = "Render days in this IANA timezone (e.g. Europe/Berlin) instead of the local one")]
+    timezone: Option<String>,
+
+    #[arg(long, help = "Don't draw tagged time ranges over the bars", action = ArgAction::SetTrue)]
+    no_tags: Option<bool>,
+
+    #[arg(long, help = "Draw context switches per hour right of the bars and deep work blocks left of them", action = ArgAction::SetTrue)]
+    switches: Option<bool>,
+
+    #[arg(short, long, help = "Color by window class or by category from the config file, splits the heatmap into one grid per class or category", value_enum)]
+    by: Option<Group>,
+
+    #[arg(long, help = "Draw the active minutes per hour of each weekday averaged over the range instead of the days", action = ArgAction::SetTrue)]
+    heatmap: Option<bool>,
+
+    #[arg(short = 'n', long, help = "Only draw heatmaps of the top N classes or categories, with --class the number of titles listed, defaults to 10")]
+    top: Option<usize>,
+
+    #[arg(long, help = "Samples up to this many seconds apart are drawn as continuous, raise it for samples recorded less often than every 10 s", default_value_t = MAX_GAP)]
+    max_gap: i64,
+
+    #[arg(long, help = "Only draw this window class, split by window title")]
+    class: Option<String>,
+
+    #[arg(long, help = "Split --class by the first capture group of this regular expression or the whole match instead of the whole title, e.g. to group by domain or project")]
+    title_pattern: Option<String>,
+
+    #[arg(long, help = "Draw the active time of every day of the year containing start date as a calendar", action = ArgAction::SetTrue)]
+    calendar: Option<bool>,
+
+    #[arg(long, help = "Draw the hours of each day as bars stacked by class or category", action = ArgAction::SetTrue)]
+    bars: Option<bool>,
+
+    #[arg(long, help = "Draw the share of each class or category of the whole range as a pie chart", action = ArgAction::SetTrue)]
+    pie: Option<bool>,
+
+    #[arg(long, help = "Like --pie but as a donut chart with the total in the middle", action = ArgAction::SetTrue)]
+    donut: Option<bool>,
 }
 
 
@@ -76,180 +236,610 @@ fn fmt(ts: Duration) -> String {
     format!("{}:{:0>2}:{:0>2} ", ts.num_hours(), ts.num_minutes() % 60, ts.num_seconds() % 60)
 }
 
-fn datestr_to_local(s: &str, end: bool) -> Result<DateTime<Local>, ()> {
-    let parts = s.split('-');
-    let parts: Vec<&str> = parts.collect();
-    if parts.len() != 3 {
-        return Err(());
+fn hour_lines<T: TimeZone>(backend: &DrawingArea<BitMapBackend<'_>, plotters::coord::Shift>, day: &Day<T>, p_per_h: f32) {
+    let mut marks: Vec<i64> = day.hour_marks().iter().map(|(offset, _)| *offset).collect();
+    marks.push(day.seconds());
+    for offset in marks {
+        let y = calculate_y(offset, p_per_h) + scaled(DATE_MARGIN);
+        backend.draw(&PathElement::new(vec![(0, y), (scaled(DAY_WIDTH), y)], theme().grid)).unwrap();
+    }
+    for (offset, hour) in day.hour_marks() {
+        if hour == 12 {
+            let y = calculate_y(offset, p_per_h) + scaled(DATE_MARGIN);
+            backend.draw(&PathElement::new(vec![(0, y - 1), (scaled(DAY_WIDTH), y - 1)], theme().grid)).unwrap();
+            backend.draw(&PathElement::new(vec![(0, y + 1), (scaled(DAY_WIDTH), y + 1)], theme().grid)).unwrap();
+        }
+    }
+    // mark where the clocks were changed so shifted hours are recognizable
+    for offset in day.transitions() {
+        let y = calculate_y(offset, p_per_h) + scaled(DATE_MARGIN);
+        backend.draw(&PathElement::new(vec![(0, y), (scaled(DAY_WIDTH), y)], theme().transition)).unwrap();
+    }
+    backend.present().unwrap();
+}
+
+
+/// Draws the tagged ranges of `day` as labeled bands over the bar.
+fn tag_bands<T: TimeZone>(backend: &DrawingArea<BitMapBackend<'_>, plotters::coord::Shift>, day: &Day<T>, tags: &[Annotation], p_per_h: f32) {
+    let style = font(TAG_FONT_SIZE);
+    let (start, end) = (day.start.timestamp(), day.end.timestamp());
+    for tag in tags.iter().filter(|t| t.end > start && t.start < end) {
+        let y0 = calculate_y(day.offset(tag.start.max(start)), p_per_h) + scaled(DATE_MARGIN);
+        let y1 = calculate_y(day.offset(tag.end.min(end)), p_per_h) + scaled(DATE_MARGIN);
+        backend.draw(&Rectangle::new([(BAR_MARGIN, y0), (BAR_MARGIN + bar_width(), y1)], theme().tags.mix(0.3).filled())).unwrap();
+        backend.draw(&Rectangle::new([(BAR_MARGIN, y0), (BAR_MARGIN + bar_width(), y1)], theme().tags)).unwrap();
+        // only label ranges that started on this day
+        if tag.start >= start {
+            backend.draw(&Text::new(tag.tag.clone(), (BAR_MARGIN + 2, y0 + 1), style.clone())).unwrap();
+        }
     }
+}
 
-    let y: i32 = parts[0].parse().unwrap();
-    let m: u32 = parts[1].parse().unwrap();
-    let d: u32 = parts[2].parse().unwrap();
-    if end {
-        return Ok(Local.with_ymd_and_hms(y, m, d, 23, 59, 59).unwrap());
+/// Draws the number of context switches of each hour as bars in the right
+/// margin and marks deep work blocks in the left one.
+fn switch_overlay<T: TimeZone>(backend: &DrawingArea<BitMapBackend<'_>, plotters::coord::Shift>, day: &Day<T>, analysis: &Analysis, p_per_h: f32) {
+    let mut per_hour = vec![0; ((day.seconds() + 3599) / 3600) as usize];
+    for ts in &analysis.switches {
+        per_hour[(day.offset(*ts) / 3600) as usize] += 1;
+    }
+    for (hour, count) in per_hour.into_iter().enumerate().filter(|(_, c)| *c > 0) {
+        let y0 = calculate_y(hour as i64 * 3600, p_per_h) + scaled(DATE_MARGIN) + 1;
+        let y1 = calculate_y((hour as i64 + 1) * 3600, p_per_h) + scaled(DATE_MARGIN) - 1;
+        let width = ((BAR_MARGIN - 2) as usize * count.min(MAX_SWITCHES) / MAX_SWITCHES).max(2) as i32;
+        let x = BAR_MARGIN + bar_width() + 1;
+        backend.draw(&Rectangle::new([(x, y0), (x + width, y1)], theme().switches.filled())).unwrap();
+    }
+    for block in analysis.deep_work.iter().filter(|b| b.seconds() >= MIN_DEEP_WORK) {
+        let y0 = calculate_y(day.offset(block.start), p_per_h) + scaled(DATE_MARGIN);
+        let y1 = calculate_y(day.offset(block.end), p_per_h) + scaled(DATE_MARGIN);
+        backend.draw(&Rectangle::new([(BAR_MARGIN - 6, y0), (BAR_MARGIN - 2, y1)], theme().deep_work.filled())).unwrap();
     }
-    return Ok(Local.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap());
 }
 
+/// The color of a cell filled to `f`, between empty at 0 and `color` at 1.
+fn shade(color: &RGBColor, f: f64) -> RGBColor {
+    let empty = theme().heatmap_empty;
+    let mix = |empty: u8, full: u8| (empty as f64 + (full as f64 - empty as f64) * f) as u8;
+    RGBColor(mix(empty.0, color.0), mix(empty.1, color.1), mix(empty.2, color.2))
+}
 
-fn hour_lines(backend: &DrawingArea<BitMapBackend<'_>, plotters::coord::Shift>, p_per_h: f32) {
-    for h in 0..25 {
-        let y = (h as f32 * p_per_h + DATE_MARGIN as f32) as i32;
-        backend.draw(&PathElement::new(vec![(0, y), (DAY_WIDTH, y)], &BLACK)).unwrap();
-        if h == 12 {
-            backend.draw(&PathElement::new(vec![(0, y - 1), (DAY_WIDTH, y - 1)], &BLACK)).unwrap();
-            backend.draw(&PathElement::new(vec![(0, y + 1), (DAY_WIDTH, y + 1)], &BLACK)).unwrap();
+/// Perceived brightness of `color` from 0 to 255.
+fn brightness(color: &RGBColor) -> u32 {
+    (299 * color.0 as u32 + 587 * color.1 as u32 + 114 * color.2 as u32) / 1000
+}
+
+/// Adds the seconds of `span` to the hours of the weekdays it covers.
+fn add_to_grid<T: TimeZone>(grid: &mut Grid, span: &Span, tz: &T) {
+    let mut t = span.start;
+    while t < span.end {
+        let time = tz.timestamp_opt(t, 0).unwrap();
+        let end = (t + 3600 - (time.minute() * 60 + time.second()) as i64).min(span.end);
+        grid[time.weekday().num_days_from_monday() as usize][time.hour() as usize] += end - t;
+        t = end;
+    }
+}
+
+/// Draws one 7x24 grid per entry of `grids`, each cell shows the active
+/// minutes of that hour averaged over the number of such weekdays in the range.
+fn heatmap(grids: &[(String, RGBColor, Grid)], weekdays: &[i64; 7], title: &str) {
+    let style = font(FONT_SIZE);
+    let cell_style = font(HEATMAP_FONT_SIZE);
+    let grid_height = 9 * text_block();
+    let width = scaled(TIME_MARGIN) + 24 * text_block() + TEXT_MARGIN;
+    let height = text_block() + grids.len() as i32 * grid_height;
+
+    let backend = BitMapBackend::new("chart.png", (width as u32, height as u32)).into_drawing_area();
+    backend.fill(&theme().background).unwrap();
+    backend.draw(&Text::new(title.to_string(), (TEXT_MARGIN, TEXT_MARGIN), style.clone())).unwrap();
+    for (i, (name, color, grid)) in grids.iter().enumerate() {
+        let top = text_block() + i as i32 * grid_height;
+        let total: i64 = grid.iter().flatten().sum();
+        backend.draw(&Text::new(format!("{} {}", name, fmt(Duration::seconds(total))), (TEXT_MARGIN, top + TEXT_MARGIN), style.clone())).unwrap();
+        for hour in 0..24 {
+            let x = scaled(TIME_MARGIN) + hour * text_block();
+            backend.draw(&Text::new(format!("{:>2}", hour), (x + 2, top + text_block() + TEXT_MARGIN), cell_style.clone())).unwrap();
+        }
+        for (wd, hours) in grid.iter().enumerate() {
+            let y = top + (wd as i32 + 2) * text_block();
+            let weekday = Weekday::try_from(wd as u8).unwrap();
+            backend.draw(&Text::new(format!("{}", weekday), (TEXT_MARGIN, y + TEXT_MARGIN), style.clone())).unwrap();
+            for (hour, secs) in hours.iter().enumerate() {
+                let minutes = secs / 60 / weekdays[wd].max(1);
+                // repeated hours of DST transitions can exceed 60 minutes
+                let f = (minutes as f64 / 60.0).min(1.0);
+                let fill = shade(color, f);
+                let x = scaled(TIME_MARGIN) + hour as i32 * text_block();
+                backend.draw(&Rectangle::new([(x + 1, y + 1), (x + text_block() - 1, y + text_block() - 1)], fill.filled())).unwrap();
+                if minutes > 0 {
+                    // the empty color differs between themes, pick the text by brightness,
+                    // about half filled cells of the gray theme switch to black
+                    let text = cell_style.clone().color(if brightness(&fill) > 90 { &BLACK } else { &WHITE });
+                    backend.draw(&Text::new(format!("{:>2}", minutes), (x + 2, y + TEXT_MARGIN + 2), text)).unwrap();
+                }
+            }
         }
-        backend.present().unwrap();
     }
+    backend.present().unwrap();
 }
 
+/// Draws the active seconds of every day as a calendar with one column per
+/// week, the days with any activity are never drawn empty.
+fn calendar(totals: &[(NaiveDate, i64)], title: &str) {
+    let style = font(FONT_SIZE);
+    let small = font(HEATMAP_FONT_SIZE);
+    let monday = totals[0].0.week(Weekday::Mon).first_day();
+    let column = |date: NaiveDate| ((date - monday).num_days() / 7) as i32;
+    let weeks = column(totals.last().unwrap().0) + 1;
+    let width = scaled(TIME_MARGIN) + weeks * CALENDAR_CELL + TEXT_MARGIN;
+    let top = 2 * text_block();
+    let height = top + 7 * CALENDAR_CELL + 2 * text_block();
+    let fraction = |secs: i64| if secs == 0 { 0.0 } else { (secs as f64 / (CALENDAR_FULL_HOURS * 3600) as f64).clamp(0.15, 1.0) };
+
+    let backend = BitMapBackend::new("chart.png", (width as u32, height as u32)).into_drawing_area();
+    backend.fill(&theme().background).unwrap();
+    let total: i64 = totals.iter().map(|(_, secs)| secs).sum();
+    let active = totals.iter().filter(|(_, secs)| *secs > 0).count();
+    let line = format!("{}: {}on {} days", title, fmt(Duration::seconds(total)), active);
+    backend.draw(&Text::new(line, (TEXT_MARGIN, TEXT_MARGIN), style.clone())).unwrap();
+    for wd in [Weekday::Mon, Weekday::Wed, Weekday::Fri] {
+        let y = top + wd.num_days_from_monday() as i32 * CALENDAR_CELL;
+        backend.draw(&Text::new(format!("{}", wd), (TEXT_MARGIN, y), small.clone())).unwrap();
+    }
+    for (date, secs) in totals {
+        let x = scaled(TIME_MARGIN) + column(*date) * CALENDAR_CELL;
+        let y = top + date.weekday().num_days_from_monday() as i32 * CALENDAR_CELL;
+        if date.day() == 1 {
+            let month = Month::from_u32(date.month()).unwrap().name();
+            backend.draw(&Text::new(format!("{:.3}", month), (x, text_block()), style.clone())).unwrap();
+        }
+        backend.draw(&Rectangle::new([(x + 1, y + 1), (x + CALENDAR_CELL - 1, y + CALENDAR_CELL - 1)], shade(&theme().heatmap, fraction(*secs)).filled())).unwrap();
+    }
 
-fn calculate_y(ts: i64, height: i32) -> i32 {
+    let y = top + 7 * CALENDAR_CELL + TEXT_MARGIN;
+    let mut x = scaled(TIME_MARGIN);
+    backend.draw(&Text::new("0h".to_string(), (x, y), small.clone())).unwrap();
+    x += 2 * CALENDAR_CELL;
+    for hours in 0..=CALENDAR_FULL_HOURS / 2 {
+        let fill = shade(&theme().heatmap, fraction(hours * 2 * 3600));
+        backend.draw(&Rectangle::new([(x + 1, y + 1), (x + CALENDAR_CELL - 1, y + CALENDAR_CELL - 1)], fill.filled())).unwrap();
+        x += CALENDAR_CELL;
+    }
+    backend.draw(&Text::new(format!("{}h+", CALENDAR_FULL_HOURS), (x + TEXT_MARGIN, y), small.clone())).unwrap();
+    backend.present().unwrap();
+}
 
-    let dt = DateTime::<Utc>::from_naive_utc_and_offset(NaiveDateTime::from_timestamp_opt(ts, 0).unwrap(), Utc).with_timezone(&Local);
-    let hour_height = height as f32 / 24.0;
-    let y = hour_height * dt.hour() as f32 + hour_height * (dt.minute() as f32 / 60.0);
-    return y as i32;
+/// Hatches the rectangle from `(x0, y0)` to `(x1, y1)` with diagonal lines
+/// to mark times the daemon wasn't running.
+fn hatch(backend: &DrawingArea<BitMapBackend<'_>, plotters::coord::Shift>, (x0, y0): (i32, i32), (x1, y1): (i32, i32)) {
+    let color = theme().text.mix(0.6);
+    // lines of constant x + y, aligned across rectangles so adjacent ones match
+    let first = (x0 + y0).div_euclid(HATCH_STEP) * HATCH_STEP;
+    for c in (first..=x1 + y1).step_by(HATCH_STEP as usize) {
+        let (xa, xb) = ((c - y1).max(x0), (c - y0).min(x1));
+        if xa < xb {
+            backend.draw(&PathElement::new(vec![(xa, c - xa), (xb, c - xb)], color)).unwrap();
+        }
+    }
+    backend.draw(&Rectangle::new([(x0, y0), (x1, y1)], color)).unwrap();
 }
 
-fn main() {
-    let mut db = if let Ok(s) = env::var("TRACKING_DB") {
-        s
-    } else {
-        "tracking.db".to_string()
-    };
+/// Number of lines `draw_legend` draws.
+fn legend_lines(count_data: &[TaskClass], no_data: i64) -> i32 {
+    count_data.len() as i32 + if no_data > 0 { 1 } else { 0 }
+}
+
+/// Lists the classes with their color, time and share of the total, followed
+/// by the time without data if there is any.
+fn draw_legend(backend: &DrawingArea<BitMapBackend<'_>, plotters::coord::Shift>, count_data: &[TaskClass], total_secs: i64, no_data: i64) {
+    let style = font(FONT_SIZE);
+    let mut y = 0;
+    for c in count_data {
+        let s = ShapeStyle{color: c.color.to_rgba(), filled: true, stroke_width: 1};
+        backend.draw(&Rectangle::new([(TEXT_MARGIN, y + TEXT_MARGIN), (text_block() - TEXT_MARGIN, y + text_block() - TEXT_MARGIN)], s)).unwrap();
+        let mut line = format!(": {} {} ({:.2}%)", c.class, fmt(Duration::seconds(c.secs)), 100.0 * c.secs as f32 / total_secs as f32);
+        if y == 0 {
+            line += " total: ";
+            line += &fmt(Duration::seconds(total_secs));
+        }
+        backend.draw(&Text::new(line, (text_block(), y + TEXT_MARGIN), style.clone())).unwrap();
+        y += text_block();
+    }
+    if no_data > 0 {
+        hatch(backend, (TEXT_MARGIN, y + TEXT_MARGIN), (text_block() - TEXT_MARGIN, y + text_block() - TEXT_MARGIN));
+        backend.draw(&Text::new(format!(": no data {}", fmt(Duration::seconds(no_data))), (text_block(), y + TEXT_MARGIN), style)).unwrap();
+    }
+}
+
+/// Draws one bar per day stacking the time of every class in the order of
+/// the legend, `secs` holds the seconds per day and class.
+fn stacked_bars(dates: &[NaiveDate], secs: &[HashMap<String, i64>], count_data: &[TaskClass], total_secs: i64, height: i32) {
+    let style = font(FONT_SIZE);
+    let small = font(HEATMAP_FONT_SIZE);
+    let max_secs = secs.iter().map(|c| c.values().sum::<i64>()).max().unwrap_or_default();
+    let max_hours = ((max_secs + 3599) / 3600).max(1);
+    let p_per_h = height as f32 / max_hours as f32;
+    // label fewer hours when they are too close to each other
+    let step = (text_block() as f32 / p_per_h).ceil().max(1.0) as i64;
+    let legend_height = text_block() * count_data.len() as i32 + LEGEND_MARGIN;
+    let width = (scaled(TIME_MARGIN) + dates.len() as i32 * STACK_WIDTH + TEXT_MARGIN).max(text_block() * 20);
+    let bottom = scaled(DATE_MARGIN) + height;
+    let total_height = bottom + text_block() + legend_height;
+
+    let backend = BitMapBackend::new("chart.png", (width as u32, total_height as u32)).into_drawing_area();
+    backend.fill(&theme().background).unwrap();
+    for h in (0..=max_hours).step_by(step as usize) {
+        let y = bottom - calculate_y(h * 3600, p_per_h);
+        backend.draw(&PathElement::new(vec![(scaled(TIME_MARGIN), y), (width, y)], theme().grid)).unwrap();
+        backend.draw(&Text::new(format!("{:>2}h", h), (TEXT_MARGIN, y - TEXT_MARGIN), style.clone())).unwrap();
+    }
+    for (i, (date, day)) in dates.iter().zip(secs).enumerate() {
+        let x = scaled(TIME_MARGIN) + i as i32 * STACK_WIDTH;
+        let mut stacked = 0;
+        for c in count_data {
+            let Some(secs) = day.get(&c.class) else {
+                continue;
+            };
+            let y0 = bottom - calculate_y(stacked, p_per_h);
+            stacked += secs;
+            let y1 = bottom - calculate_y(stacked, p_per_h);
+            backend.draw(&Rectangle::new([(x + STACK_MARGIN, y1), (x + STACK_WIDTH - STACK_MARGIN, y0)], c.color.filled())).unwrap();
+        }
+        backend.draw(&Text::new(format!("{}", date.format("%m-%d")), (x + 2, bottom + TEXT_MARGIN), small.clone())).unwrap();
+    }
+    draw_legend(&backend.clone().shrink((0, bottom + text_block()), (width as u32, legend_height as u32)), count_data, total_secs, 0);
+    backend.present().unwrap();
+}
+
+/// Draws the share of every class of the whole range as a pie, or as a donut
+/// with the total in the middle.
+fn pie(count_data: &[TaskClass], total_secs: i64, donut: bool, title: &str) {
+    let style = font(FONT_SIZE);
+    let legend_height = text_block() * count_data.len() as i32 + LEGEND_MARGIN;
+    let size = PIE_SIZE + 2 * scaled(DATE_MARGIN);
+    let width = size.max(text_block() * 20);
+    let height = size + legend_height;
+
+    let backend = BitMapBackend::new("chart.png", (width as u32, height as u32)).into_drawing_area();
+    backend.fill(&theme().background).unwrap();
+    backend.draw(&Text::new(title.to_string(), (TEXT_MARGIN, TEXT_MARGIN), style.clone())).unwrap();
+    let center = (width / 2, size / 2);
+    let radius = (PIE_SIZE / 2) as f64;
+    let sizes: Vec<f64> = count_data.iter().map(|c| c.secs as f64).collect();
+    let colors: Vec<RGBColor> = count_data.iter().map(|c| c.color).collect();
+    let labels: Vec<&str> = count_data.iter().map(|_| "").collect();
+    let mut chart = Pie::new(&center, &radius, &sizes, &colors, &labels);
+    chart.start_angle(-90.0);
+    if donut {
+        chart.donut_hole(radius / 2.0);
+    }
+    backend.draw(&chart).unwrap();
+    if donut {
+        let total = fmt(Duration::seconds(total_secs));
+        let (w, h) = backend.estimate_text_size(total.trim_end(), &style).unwrap();
+        backend.draw(&Text::new(total, (center.0 - w as i32 / 2, center.1 - h as i32 / 2), style.clone())).unwrap();
+    }
+    draw_legend(&backend.clone().shrink((0, size), (width as u32, legend_height as u32)), count_data, total_secs, 0);
+    backend.present().unwrap();
+}
 
-    let now = Local::now();
+/// Draws one row per day with the time on the horizontal axis, tagged ranges
+/// and the total of each day in a column on the right, 24 hours are
+/// `hours_width` pixels wide and every row is `row_height` pixels high.
+fn horizontal<T: TimeZone>(days: &[Day<T>], day_spans: &[Vec<Span>], day_gaps: &[Vec<(i64, i64)>], color_map: &HashMap<String, RGBColor>, tags: &[Annotation], count_data: &[TaskClass], (hours_width, row_height): (i32, i32)) {
+    let style = font(FONT_SIZE);
+    // text of rows lower than a line is shrunk to fit
+    let max_size = (row_height - 2 * TEXT_MARGIN) * FONT_SIZE / theme().font_size;
+    let row_style = font(FONT_SIZE.min(max_size).max(8));
+    let tag_style = font(TAG_FONT_SIZE.min(max_size).max(8));
+    // days around DST transitions are 23 or 25 hours long, make room for the longest one
+    let p_per_h = hours_width as f32 / 24.0;
+    let max_hours = days.iter().map(|d| (d.seconds() + 3599) / 3600).max().unwrap();
+    let plot_width = calculate_y(max_hours * 3600, p_per_h);
+    let no_data = day_gaps.iter().flatten().map(|g| g.1 - g.0).sum();
+    let legend_height = text_block() * legend_lines(count_data, no_data) + LEGEND_MARGIN;
+    let rows_height = days.len() as i32 * row_height;
+    let width = scaled(ROW_LABEL_WIDTH) + plot_width + scaled(ROW_TOTAL_WIDTH);
+    let height = scaled(DATE_MARGIN) + rows_height + LEGEND_MARGIN + legend_height;
+    let x = |offset: i64| scaled(ROW_LABEL_WIDTH) + calculate_y(offset, p_per_h);
+
+    let backend = BitMapBackend::new("chart.png", (width as u32, height as u32)).into_drawing_area();
+    backend.fill(&theme().background).unwrap();
+    // label fewer hours when they are too close to each other, with the wall
+    // clock of the longest day, which shows where the clocks were changed
+    let step = (3.0 * text_block() as f32 / p_per_h).ceil().max(1.0) as i64;
+    let longest = days.iter().max_by_key(|d| d.seconds()).unwrap();
+    for h in (0..max_hours).step_by(step as usize).filter(|h| calculate_y(h * 3600, p_per_h) + 2 * text_block() <= plot_width) {
+        backend.draw(&Text::new(longest.clock(h * 3600), (x(h * 3600) + 2, TEXT_MARGIN), style.clone())).unwrap();
+    }
+    backend.draw(&Text::new("Total".to_string(), (scaled(ROW_LABEL_WIDTH) + plot_width + TEXT_MARGIN, TEXT_MARGIN), style.clone())).unwrap();
+
+    for (i, ((day, spans), gaps)) in days.iter().zip(day_spans).zip(day_gaps).enumerate() {
+        let y0 = scaled(DATE_MARGIN) + i as i32 * row_height;
+        let y1 = y0 + row_height;
+        let label = format!("{}", day.date.format("%a %Y-%m-%d"));
+        backend.draw(&Text::new(label, (TEXT_MARGIN, y0 + TEXT_MARGIN), row_style.clone())).unwrap();
+        backend.draw(&PathElement::new(vec![(0, y1), (width, y1)], theme().grid)).unwrap();
+        for (offset, hour) in day.hour_marks() {
+            let color = if hour == 12 { theme().grid.stroke_width(3) } else { theme().grid.stroke_width(1) };
+            backend.draw(&PathElement::new(vec![(x(offset), y0), (x(offset), y1)], color)).unwrap();
+        }
+        for offset in day.transitions() {
+            backend.draw(&PathElement::new(vec![(x(offset), y0), (x(offset), y1)], theme().transition)).unwrap();
+        }
+        for gap in gaps {
+            hatch(&backend, (x(day.offset(gap.0)), y0 + 2), (x(day.offset(gap.1)), y1 - 2));
+        }
+        for span in spans {
+            let (x0, x1) = (x(day.offset(span.start)), x(day.offset(span.end)));
+            backend.draw(&Rectangle::new([(x0, y0 + 2), (x1.max(x0 + 1), y1 - 2)], color_map[&span.class].filled())).unwrap();
+        }
+        let (start, end) = (day.start.timestamp(), day.end.timestamp());
+        for tag in tags.iter().filter(|t| t.end > start && t.start < end) {
+            let (x0, x1) = (x(day.offset(tag.start.max(start))), x(day.offset(tag.end.min(end))));
+            backend.draw(&Rectangle::new([(x0, y0 + 1), (x1, y1 - 1)], theme().tags.mix(0.3).filled())).unwrap();
+            backend.draw(&Rectangle::new([(x0, y0 + 1), (x1, y1 - 1)], theme().tags)).unwrap();
+            if tag.start >= start {
+                backend.draw(&Text::new(tag.tag.clone(), (x0 + 2, y0 + 2), tag_style.clone())).unwrap();
+            }
+        }
+        let total = Duration::seconds(spans.iter().map(|s| s.seconds()).sum());
+        backend.draw(&Text::new(fmt(total), (scaled(ROW_LABEL_WIDTH) + plot_width + TEXT_MARGIN, y0 + TEXT_MARGIN), row_style.clone())).unwrap();
+    }
+    let total_secs = count_data.iter().map(|c| c.secs).sum();
+    draw_legend(&backend.clone().shrink((0, scaled(DATE_MARGIN) + rows_height + LEGEND_MARGIN), (width as u32, legend_height as u32)), count_data, total_secs, no_data);
+    backend.present().unwrap();
+}
+
+fn calculate_y(offset: i64, p_per_h: f32) -> i32 {
+    (offset as f32 / 3600.0 * p_per_h) as i32
+}
+
+fn main() {
     let args = Args::parse();
-    if args.database.is_some() {
-        db = args.database.unwrap();
+    match args.timezone.clone() {
+        Some(name) => {
+            let tz: Tz = name.parse().unwrap_or_else(|e| {
+                eprintln!("Unknown timezone {}: {}", name, e);
+                process::exit(1);
+            });
+            chart(args, tz);
+        },
+        None => chart(args, Local),
     }
+}
 
-    let mut end = if args.end.is_some() {
-        args.end.unwrap()
+fn chart<T: TimeZone>(args: Args, tz: T) where T::Offset: Display {
+    let db = db::path(args.database);
+    let config = Config::load(&config::path(args.config)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    set_style(&config.chart).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let show_switches = args.switches.is_some() && args.switches.unwrap();
+
+    let today = Utc::now().with_timezone(&tz).date_naive();
+
+    let mut start = if let Some(s) = args.start {
+        parse_date(&s).expect("Start date must be of format YYYY-MM-DD")
     } else {
-        format!("{}-{}-{}", now.year(), now.month(), now.day())
+        today
     };
 
-    let mut start = if args.start.is_some() {
-        args.start.unwrap()
+    let mut end = if let Some(e) = args.end {
+        parse_date(&e).expect("End date must be of format YYYY-MM-DD")
     } else {
-        format!("{}-{}-{}", now.year(), now.month(), now.day())
+        today
     };
 
-    let now = datestr_to_local(&start, false).unwrap();
-
     if args.week.is_some() && args.week.unwrap() {
-        let mut check_day = now.clone();
-        while !check_day.weekday().eq(&chrono::Weekday::Mon) {
-            check_day = check_day.checked_sub_days(Days::new(1)).unwrap();
-        }
-        start = format!("{}-{}-{}", check_day.year(), check_day.month(), check_day.day());
-        check_day = check_day.checked_add_days(Days::new(6)).unwrap();
-        end = format!("{}-{}-{}", check_day.year(), check_day.month(), check_day.day());
+        start = start.week(Weekday::Mon).first_day();
+        end = start.week(Weekday::Mon).last_day();
     }
 
     if args.month.is_some() && args.month.unwrap() {
-        start = format!("{}-{}-1", now.year(), now.month());
-        let mut check_day = now.clone();
-        let month = now.month();
-        while check_day.month() == month {
-            check_day = check_day.checked_add_days(Days::new(1)).unwrap();
-        }
-        check_day = check_day.checked_sub_days(Days::new(1)).unwrap();
-        end = format!("{}-{}-{}", check_day.year(), check_day.month(), check_day.day());
+        start = start.with_day(1).unwrap();
+        end = start.checked_add_months(Months::new(1)).unwrap().pred_opt().unwrap();
+    }
+
+    let show_calendar = args.calendar.is_some() && args.calendar.unwrap();
+    if show_calendar {
+        start = NaiveDate::from_ymd_opt(start.year(), 1, 1).unwrap();
+        end = NaiveDate::from_ymd_opt(start.year(), 12, 31).unwrap();
     }
 
     if args.today.is_some() && args.today.unwrap() {
-        end = start.clone();
+        end = start;
     }
 
-    let start = datestr_to_local(&start, false).unwrap();
-    let end = datestr_to_local(&end, true).unwrap();
+    let days = day::days(&tz, start, end);
+    let first_day = days.first().expect("End date must not be before start date");
+    let last_day = days.last().unwrap();
+    let (start, end) = (first_day.start.clone(), last_day.end.clone());
     println!("start: {}\nend:   {}", start, end);
 
-    let conn = Connection::open(&db).unwrap();
-    let query = if args.idle.is_some() && args.idle.unwrap() {
-        format!("select class, ts FROM tracking where ts > {} and ts < {} order by ts asc;", start.timestamp(), end.timestamp())
+    let conn = db::open_read_only(&db).unwrap_or_else(|e| {
+        eprintln!("Could not open {}: {}", db, e);
+        process::exit(1);
+    });
+    let filter = db::Filter {
+        start: start.timestamp(),
+        end: end.timestamp(),
+        idle: args.idle.is_some() && args.idle.unwrap(),
+        host: args.host,
+    };
+    let mut res = db::samples(&conn, &filter).unwrap();
+    let tags = if args.no_tags.is_some() && args.no_tags.unwrap() {
+        vec![]
     } else {
-        format!("select class, ts FROM tracking where ts > {} and ts < {} and idle not like 1 order by ts asc;", start.timestamp(), end.timestamp())
+        annotations::load(&conn, filter.start, filter.end).unwrap()
     };
-    let mut stmt = conn.prepare(&query).unwrap();
 
-    let res = stmt.query_map((), |row| {
-        Ok(Row {
-            class: row.get(0)?,
-            ts:    row.get(1)?,
-        })
-    }).unwrap();
+    let title_pattern = args.title_pattern.as_ref().map(|p| Regex::new(p).unwrap_or_else(|e| {
+        eprintln!("Invalid title pattern {}: {}", p, e);
+        process::exit(1);
+    }));
+    let title_key = |title: &str| -> String {
+        let key = match &title_pattern {
+            Some(re) => re.captures(title).map(|c| c.get(1).or(c.get(0)).unwrap().as_str().to_string()),
+            None => Some(title.to_string()),
+        };
+        key.filter(|k| !k.is_empty()).unwrap_or(OTHER_TITLES.to_string())
+    };
+    // with --class the titles are drawn instead of the classes, only the most
+    // used ones get their own color
+    let mut top_titles: Vec<(String, i64)> = vec![];
+    if let Some(class) = &args.class {
+        res.retain(|s| s.class.eq_ignore_ascii_case(class));
+        for row in &res {
+            let key = title_key(&row.title);
+            match top_titles.iter_mut().find(|t| t.0 == key) {
+                Some(t) => t.1 += 1,
+                None => top_titles.push((key, 1)),
+            }
+        }
+        top_titles.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        top_titles.truncate(args.top.unwrap_or(TOP_TITLES));
+    }
 
-    let query = if args.idle.is_some() && args.idle.unwrap() {
-        format!("select class, count (*) FROM tracking where ts > {} and ts < {} group by class order by count (*) desc;", start.timestamp(), end.timestamp())
-    } else {
-        format!("select class, count (*) FROM tracking where ts > {} and ts < {} and idle not like 1 group by class order by count (*) desc;", start.timestamp(), end.timestamp())
+    let group = |class: &str, title: &str| -> String {
+        if args.class.is_some() {
+            let key = title_key(title);
+            return if top_titles.iter().any(|t| t.0 == key) { key } else { OTHER_TITLES.to_string() };
+        }
+        match args.by {
+            Some(Group::Category) => config.category(class).to_string(),
+            _ => class.to_string(),
+        }
     };
-    let mut stmt = conn.prepare(&query).unwrap();
 
-    let counts = stmt.query_map((), |row| {
-        Ok(TaskClass {
-            class: row.get(0)?,
-            count: row.get(1)?,
-            color: BLACK
-        })
-    }).unwrap();
+    // each span lasts until the next sample unless they are too far apart, so
+    // bars are solid at any height and durations follow the timestamps
+    let spans: Vec<Span> = spans::merge_by_gap(&res, args.max_gap, |a, b| group(&a.class, &a.title) == group(&b.class, &b.title)).into_iter().map(|mut s| {
+        s.class = group(&s.class, &s.title);
+        s
+    }).collect();
+    let day_spans: Vec<Vec<Span>> = days.iter().map(|d| spans::clip(&spans, d.start.timestamp(), d.end.timestamp())).collect();
+    // times the daemon wasn't running are hatched to tell them apart from idle ones
+    let gaps = runs::no_data(&conn, filter.start, filter.end, Utc::now().timestamp(), &filter.host).unwrap();
+    let day_gaps: Vec<Vec<(i64, i64)>> = days.iter().map(|d| runs::clip(&gaps, d.start.timestamp(), d.end.timestamp())).collect();
+    let no_data: i64 = gaps.iter().map(|g| g.1 - g.0).sum();
+
+    let mut counts: Vec<TaskClass> = vec![];
+    for span in day_spans.iter().flatten() {
+        if let Some(c) = counts.iter_mut().find(|c| c.class == span.class) {
+            c.secs += span.seconds();
+        } else {
+            counts.push(TaskClass { class: span.class.clone(), secs: span.seconds(), color: BLACK });
+        }
+    }
+    counts.sort_by_key(|c| std::cmp::Reverse(c.secs));
 
     let mut count_data = vec![];
     let mut ci = 0;
     let mut color_map = HashMap::new();
-    let mut total_count = 0;
+    let mut total_secs = 0;
 
-    counts.for_each(|c| {
-        let c = c.unwrap();
-        let color = if ci >= COLORS.len() {
+    counts.into_iter().for_each(|c| {
+        let color = if ci >= theme().colors.len() {
             let mut rng = thread_rng();
             let gray = rng.gen_range(10..245);
             RGBColor(gray, gray, gray)
         } else {
-            COLORS[ci]
+            theme().colors[ci]
         };
         ci += 1;
-        count_data.push(TaskClass{class: c.class.clone(), count: c.count, color});
-        total_count += c.count;
+        count_data.push(TaskClass{class: c.class.clone(), secs: c.secs, color});
+        total_secs += c.secs;
         color_map.insert(c.class, color);
     });
 
 
+    if show_calendar {
+        let totals: Vec<(NaiveDate, i64)> = days.iter().zip(&day_spans).map(|(d, spans)| (d.date, spans.iter().map(|s| s.seconds()).sum())).collect();
+        calendar(&totals, &first_day.date.year().to_string());
+        return;
+    }
+
+    if args.heatmap.is_some() && args.heatmap.unwrap() {
+        let mut weekdays = [0; 7];
+        for d in &days {
+            weekdays[d.date.weekday().num_days_from_monday() as usize] += 1;
+        }
+        let split = args.by.is_some() || args.class.is_some();
+        let mut grids: Vec<(String, RGBColor, Grid)> = if split {
+            count_data.iter().map(|c| (c.class.clone(), c.color, [[0; 24]; 7])).collect()
+        } else {
+            vec![("active".to_string(), theme().heatmap, [[0; 24]; 7])]
+        };
+        for span in day_spans.iter().flatten() {
+            let grid = if split {
+                grids.iter_mut().find(|g| g.0 == span.class).unwrap()
+            } else {
+                &mut grids[0]
+            };
+            add_to_grid(&mut grid.2, span, &tz);
+        }
+        if let Some(n) = args.top {
+            grids.truncate(n);
+        }
+        heatmap(&grids, &weekdays, &format!("Active minutes per hour, {} - {}", first_day.date, last_day.date));
+        return;
+    }
+
+    if args.bars.is_some() && args.bars.unwrap() {
+        let secs: Vec<HashMap<String, i64>> = day_spans.iter().map(|spans| {
+            let mut secs = HashMap::new();
+            for span in spans {
+                *secs.entry(span.class.clone()).or_default() += span.seconds();
+            }
+            secs
+        }).collect();
+        let dates: Vec<NaiveDate> = days.iter().map(|d| d.date).collect();
+        stacked_bars(&dates, &secs, &count_data, total_secs, args.height);
+        return;
+    }
+
+    let donut = args.donut.is_some() && args.donut.unwrap();
+    if donut || (args.pie.is_some() && args.pie.unwrap()) {
+        pie(&count_data, total_secs, donut, &format!("{} - {}", first_day.date, last_day.date));
+        return;
+    }
+
     // we made it this far, we can draw stuff now
 
 
 
-    let day_height = args.height + DATE_MARGIN;
-    let day_graph_height = day_height + DAY_MARGIN + DAILY_TIME_MARGIN;
-    let mut cur = start.clone();
+    // days around DST transitions are 23 or 25 hours long, make room for the longest one
+    let p_per_h = args.height as f32 / 24.0;
+    let max_hours = days.iter().map(|d| (d.seconds() + 3599) / 3600).max().unwrap();
+    let day_height = calculate_y(max_hours * 3600, p_per_h) + scaled(DATE_MARGIN);
+    let day_graph_height = day_height + DAY_MARGIN + 6 * text_block();
     let mut day_graphs = vec![];
-    let legend_height = TEXT_BLOCK_SIZE * count_data.len() as i32 + LEGEND_MARGIN;
+    let legend_height = text_block() * legend_lines(&count_data, no_data) + LEGEND_MARGIN;
     let height = day_graph_height + legend_height;
-    let p_per_h = args.height as f32 / 24.0;
-    let style = ("hack", (TEXT_BLOCK_SIZE - 2 * TEXT_MARGIN) as f32).into_font();
+    let style = font(FONT_SIZE);
     let mut day_data = vec![];
     let mut cur_day_data = vec![];
-    let mut day_end = cur.with_hour(23).unwrap().with_minute(59).unwrap().with_second(59).unwrap().timestamp();
+    let mut cur = 0;
 
-    res.for_each(|r| {
-        let row = r.unwrap();
-        while row.ts > day_end {
-           cur = cur.checked_add_days(Days::new(1)).unwrap();
-           day_end = cur.with_hour(23).unwrap().with_minute(59).unwrap().with_second(59).unwrap().timestamp();
+    res.into_iter().for_each(|row| {
+        while !days[cur].contains(row.ts) {
+           cur += 1;
            day_data.push(cur_day_data.clone());
            cur_day_data.clear();
         }
         cur_day_data.push(row);
     });
     day_data.push(cur_day_data);
+    day_data.resize(days.len(), vec![]);
 
-    cur = start.clone();
+    if args.horizontal.is_some() && args.horizontal.unwrap() {
+        horizontal(&days, &day_spans, &day_gaps, &color_map, &tags, &count_data, (args.width, args.row_height.unwrap_or_else(text_block)));
+        return;
+    }
 
     let mut week_started_hours = Duration::seconds(0);
     let mut week_actual_hours = Duration::seconds(0);
@@ -257,48 +847,59 @@ fn main() {
     let mut month_actual_hours = Duration::seconds(0);
 
 
-    for cur_day_data in day_data {
+    for (((cur, cur_day_data), cur_day_spans), cur_day_gaps) in days.iter().zip(day_data).zip(&day_spans).zip(&day_gaps) {
 
-        let mut img = vec![0u8; (day_graph_height * DAY_WIDTH * 3) as usize];
+        let mut img = vec![0u8; (day_graph_height * scaled(DAY_WIDTH) * 3) as usize];
         {
-            let backend = BitMapBackend::with_buffer(&mut img, (DAY_WIDTH as u32, day_graph_height as u32)).into_drawing_area();
-            backend.fill(&BACKGROUND).unwrap();
-            let month = Month::from_u32(cur.month()).unwrap().name();
-            let line = format!("{:.3}, {:2}. {:.3} {}", cur.weekday(), cur.day(), month, cur.year());
+            let backend = BitMapBackend::with_buffer(&mut img, (scaled(DAY_WIDTH) as u32, day_graph_height as u32)).into_drawing_area();
+            backend.fill(&theme().background).unwrap();
+            let date = cur.date;
+            let month = Month::from_u32(date.month()).unwrap().name();
+            let line = format!("{:.3}, {:2}. {:.3} {}", date.weekday(), date.day(), month, date.year());
             backend.draw(&Text::new(line, (0, 5), style.clone())).unwrap();
-            hour_lines(&backend, p_per_h);
+            hour_lines(&backend, cur, p_per_h);
+            if show_switches {
+                switch_overlay(&backend, cur, &switches::analyze(&cur_day_data, &config), p_per_h);
+            }
+            for gap in cur_day_gaps {
+                let y0 = calculate_y(cur.offset(gap.0), p_per_h) + scaled(DATE_MARGIN);
+                let y1 = calculate_y(cur.offset(gap.1), p_per_h) + scaled(DATE_MARGIN);
+                hatch(&backend, (BAR_MARGIN, y0), (BAR_MARGIN + bar_width(), y1));
+            }
             let mut secs = 0;
-            for task in cur_day_data {
-                let y = calculate_y(task.ts, args.height) + DATE_MARGIN;
-                let color = color_map.get(&task.class).unwrap();
-                backend.draw(&PathElement::new(vec![(BAR_MARGIN, y), (BAR_MARGIN + BAR_WIDTH, y)], color)).unwrap();
-                secs += 10;
+            for span in cur_day_spans {
+                let y0 = calculate_y(cur.offset(span.start), p_per_h) + scaled(DATE_MARGIN);
+                let y1 = calculate_y(cur.offset(span.end), p_per_h) + scaled(DATE_MARGIN);
+                let color = color_map.get(&span.class).unwrap();
+                backend.draw(&Rectangle::new([(BAR_MARGIN, y0), (BAR_MARGIN + bar_width(), y1.max(y0 + 1))], color.filled())).unwrap();
+                secs += span.seconds();
             }
+            tag_bands(&backend, cur, &tags, p_per_h);
             let mut y = day_height;
             let duration = Duration::seconds(secs);
             backend.draw(&Text::new(fmt(duration), (BAR_MARGIN * 2 + TEXT_MARGIN, y + TEXT_MARGIN), style.clone())).unwrap();
             week_actual_hours = week_actual_hours.checked_add(&duration).unwrap();
             month_actual_hours = month_actual_hours.checked_add(&duration).unwrap();
-            y += TEXT_BLOCK_SIZE;
+            y += text_block();
             let duration = if duration.num_minutes() > 15 { Duration::hours(duration.num_hours() + 1) } else { Duration::hours(duration.num_hours()) };
             backend.draw(&Text::new(fmt(duration), (BAR_MARGIN * 2 + TEXT_MARGIN, y + TEXT_MARGIN), style.clone())).unwrap();
             week_started_hours = week_started_hours.checked_add(&duration).unwrap();
             month_started_hours = month_started_hours.checked_add(&duration).unwrap();
-            y += TEXT_BLOCK_SIZE;
+            y += text_block();
 
-            if cur.weekday().eq(&Weekday::Sun) || cur.date_naive().eq(&end.date_naive()) {
+            if date.weekday().eq(&Weekday::Sun) || date.eq(&last_day.date) {
                 backend.draw(&Text::new(fmt(week_actual_hours), (BAR_MARGIN * 2 + TEXT_MARGIN, y + TEXT_MARGIN), style.clone())).unwrap();
-                y += TEXT_BLOCK_SIZE;
+                y += text_block();
                 backend.draw(&Text::new(fmt(week_started_hours), (BAR_MARGIN * 2 + TEXT_MARGIN, y + TEXT_MARGIN), style.clone())).unwrap();
-                y += TEXT_BLOCK_SIZE;
+                y += text_block();
                 week_actual_hours = Duration::seconds(0);
                 week_started_hours = Duration::seconds(0);
             }
 
-            let tomorrow = cur.checked_add_days(Days::new(1)).unwrap();
-            if tomorrow.month() != cur.month() || cur.date_naive().eq(&end.date_naive()) {
+            let tomorrow = date.succ_opt().unwrap();
+            if tomorrow.month() != date.month() || date.eq(&last_day.date) {
                 backend.draw(&Text::new(fmt(month_actual_hours), (BAR_MARGIN * 2 + TEXT_MARGIN, y + TEXT_MARGIN), style.clone())).unwrap();
-                y += TEXT_BLOCK_SIZE;
+                y += text_block();
                 backend.draw(&Text::new(fmt(month_started_hours), (BAR_MARGIN * 2 + TEXT_MARGIN, y + TEXT_MARGIN), style.clone())).unwrap();
                 month_actual_hours = Duration::seconds(0);
                 month_started_hours = Duration::seconds(0);
@@ -307,55 +908,41 @@ fn main() {
             backend.present().unwrap();
         }
         day_graphs.push(img);
-        cur = cur.checked_add_days(Days::new(1)).unwrap();
     }
 
-    let width = day_graphs.len() as i32 * DAY_WIDTH + TIME_MARGIN;
+    let width = day_graphs.len() as i32 * scaled(DAY_WIDTH) + scaled(TIME_MARGIN);
 
     let mut legend = vec![0u8; (width * legend_height * 3) as usize];
 
     {
         let backend = BitMapBackend::with_buffer(&mut legend, (width as u32, legend_height as u32)).into_drawing_area();
-        backend.fill(&BACKGROUND).unwrap();
-        let mut y = 0;
-        for c in count_data {
-            let s = ShapeStyle{color: c.color.to_rgba(), filled: true, stroke_width: 1};
-            backend.draw(&Rectangle::new([(TEXT_MARGIN, y + TEXT_MARGIN), (TEXT_BLOCK_SIZE - TEXT_MARGIN, y + TEXT_BLOCK_SIZE - TEXT_MARGIN)], s)).unwrap();
-            let mut line = format!(": {} {} ({:.2}%)", c.class, fmt(Duration::seconds(c.count * 10)), 100.0 * c.count as f32 / total_count as f32);
-            if y == 0 {
-                line += " total: ";
-                line += &fmt(Duration::seconds(total_count * 10));
-            }
-            backend.draw(&Text::new(line, (TEXT_BLOCK_SIZE, y + TEXT_MARGIN), style.clone())).unwrap();
-            y += TEXT_BLOCK_SIZE;
-        }
+        backend.fill(&theme().background).unwrap();
+        draw_legend(&backend, &count_data, total_secs, no_data);
         backend.present().unwrap();
     }
 
-    let mut times = vec![0u8; (TIME_MARGIN * day_graph_height * 3) as usize];
+    let mut times = vec![0u8; (scaled(TIME_MARGIN) * day_graph_height * 3) as usize];
 
     {
-        let backend = BitMapBackend::with_buffer(&mut times, (TIME_MARGIN as u32, day_graph_height as u32)).into_drawing_area();
-        backend.fill(&BACKGROUND).unwrap();
-        let mut i = DATE_MARGIN as f32;
-        let mut h = 0;
-        while i < day_height as f32 {
-            let line = format!("{:>2}:00", h);
-            backend.draw(&Text::new(line, (TEXT_MARGIN, i as i32 - TEXT_MARGIN), style.clone())).unwrap();
-            h += 1;
-            i += p_per_h;
+        let backend = BitMapBackend::with_buffer(&mut times, (scaled(TIME_MARGIN) as u32, day_graph_height as u32)).into_drawing_area();
+        backend.fill(&theme().background).unwrap();
+        // the wall clock of the longest day, which shows where the clocks were changed
+        let longest = days.iter().max_by_key(|d| d.seconds()).unwrap();
+        for h in 0..=max_hours {
+            let y = calculate_y(h * 3600, p_per_h) + scaled(DATE_MARGIN);
+            let line = format!("{:>5}", longest.clock(h * 3600));
+            backend.draw(&Text::new(line, (TEXT_MARGIN, y - TEXT_MARGIN), style.clone())).unwrap();
         }
-        backend.draw(&Text::new("24:00", (TEXT_MARGIN, i as i32 - TEXT_MARGIN), style.clone())).unwrap();
         backend.present().unwrap();
     }
 
     {
         let mut backend = BitMapBackend::new("chart.png", (width as u32, height as u32));
-        for i in 0..day_graphs.len() {
-            backend.blit_bitmap((i as i32 * DAY_WIDTH + TIME_MARGIN, 0), (DAY_WIDTH as u32, day_graph_height as u32), &day_graphs[i]).unwrap();
+        for (i, day_graph) in day_graphs.iter().enumerate() {
+            backend.blit_bitmap((i as i32 * scaled(DAY_WIDTH) + scaled(TIME_MARGIN), 0), (scaled(DAY_WIDTH) as u32, day_graph_height as u32), day_graph).unwrap();
         }
         backend.blit_bitmap((0, day_graph_height), (width as u32, legend_height as u32), &legend).unwrap();
-        backend.blit_bitmap((0, 0), (TIME_MARGIN as u32, day_graph_height as u32), &times).unwrap();
+        backend.blit_bitmap((0, 0), (scaled(TIME_MARGIN) as u32, day_graph_height as u32), &times).unwrap();
         backend.present().unwrap();
     }
 }
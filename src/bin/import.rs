@@ -0,0 +1,214 @@
+use rusqlite::{Connection, OpenFlags};
+use rusqlite::types::Value;
+use chrono::prelude::*;
+use clap::{Parser, ValueEnum};
+use std::fs;
+use std::process;
+use tracking::activitywatch;
+use tracking::day::resolve_local;
+use tracking::db::{self, Sample};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    /// sqlite database of the original klaxa/tracking
+    Legacy,
+    /// JSON bucket export of ActivityWatch
+    Activitywatch,
+}
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to import into, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(short, long, help = "Format of the data to import", value_enum, default_value_t = Format::Legacy)]
+    format: Format,
+
+    #[arg(long, help = "Host to record the imported samples for if the data has none, defaults to this machine")]
+    host: Option<String>,
+
+    #[arg(help = "The file to import")]
+    source: String,
+}
+
+
+/// Schema of the predecessor, it recorded a sample of the focused window into
+/// this table without tracking idle time or the host.
+const LEGACY_QUERY: &str = "select window_class, title, timestamp from focus;";
+
+
+#[derive(Debug, Default)]
+struct Summary {
+    imported: usize,
+    duplicates: usize,
+    invalid: usize,
+}
+
+fn from_unix(ts: i64) -> i64 {
+    // treat very large values as milliseconds
+    if ts > 100_000_000_000 { ts / 1000 } else { ts }
+}
+
+fn to_timestamp(value: &Value) -> Option<i64> {
+    match value {
+        Value::Integer(i) => Some(from_unix(*i)),
+        Value::Real(f) => Some(from_unix(*f as i64)),
+        Value::Text(s) => {
+            let s = s.trim();
+            if let Ok(i) = s.parse::<i64>() {
+                return Some(from_unix(i));
+            }
+            if let Ok(f) = s.parse::<f64>() {
+                return f.is_finite().then(|| from_unix(f as i64));
+            }
+            if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+                return Some(dt.timestamp());
+            }
+            ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"].iter()
+                .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+                .map(|naive| resolve_local(&Local, naive).timestamp())
+        },
+        _ => None,
+    }
+}
+
+fn to_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Text(s) => Some(s.clone()),
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Real(f) => Some(f.to_string()),
+        _ => None,
+    }
+}
+
+fn import_samples(conn: &Connection, samples: &[Sample]) -> Result<Summary, String> {
+    let mut summary = Summary::default();
+    for sample in samples {
+        if db::insert_new(conn, sample).map_err(|e| e.to_string())? {
+            summary.imported += 1;
+        } else {
+            summary.duplicates += 1;
+        }
+    }
+    Ok(summary)
+}
+
+fn import_legacy(conn: &Connection, args: &Args) -> Result<Summary, String> {
+    let source = Connection::open_with_flags(&args.source, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Could not open {}: {}", args.source, e))?;
+    let mut stmt = source.prepare(LEGACY_QUERY)
+        .map_err(|e| format!("{} is not a database of klaxa/tracking: {}", args.source, e))?;
+    let rows = stmt.query_map((), |row| {
+        Ok((row.get::<_, Value>(0)?, row.get::<_, Value>(1)?, row.get::<_, Value>(2)?))
+    }).map_err(|e| e.to_string())?;
+
+    let mut samples = vec![];
+    let mut invalid = 0;
+    for row in rows {
+        let (class, title, ts) = row.map_err(|e| e.to_string())?;
+        let (class, ts) = match (to_text(&class), to_timestamp(&ts)) {
+            (Some(class), Some(ts)) => (class, ts),
+            _ => {
+                eprintln!("Skipping invalid row: {:?}, {:?}, {:?}", class, title, ts);
+                invalid += 1;
+                continue;
+            },
+        };
+        samples.push(Sample {
+            class,
+            title: to_text(&title).unwrap_or_default(),
+            idle: false,
+            ts,
+            // like the samples the daemon recorded before hosts were stored,
+            // which `db::open` attributed to this machine, so they are skipped
+            host: args.host.clone().unwrap_or_else(db::hostname),
+        });
+    }
+    let mut summary = import_samples(conn, &samples)?;
+    summary.invalid = invalid;
+    Ok(summary)
+}
+
+fn import_activitywatch(conn: &Connection, args: &Args) -> Result<Summary, String> {
+    let content = fs::read_to_string(&args.source).map_err(|e| format!("Could not read {}: {}", args.source, e))?;
+    let export = json::parse(&content).map_err(|e| format!("Could not parse {}: {}", args.source, e))?;
+    let mut samples = activitywatch::samples(&export)?;
+    for s in samples.iter_mut().filter(|s| s.host.is_empty()) {
+        s.host = args.host.clone().unwrap_or_else(db::hostname);
+    }
+    import_samples(conn, &samples)
+}
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database.clone());
+
+    let mut conn = db::open(&db).unwrap();
+    let tx = conn.transaction().unwrap();
+    let summary = match args.format {
+        Format::Legacy => import_legacy(&tx, &args),
+        Format::Activitywatch => import_activitywatch(&tx, &args),
+    };
+    let summary = summary.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    tx.commit().unwrap();
+
+    println!("Imported {} samples, skipped {} already present and {} invalid ones", summary.imported, summary.duplicates, summary.invalid);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn reads_numeric_and_date_timestamps() {
+        assert_eq!(to_timestamp(&Value::Integer(1556704800)), Some(1556704800));
+        assert_eq!(to_timestamp(&Value::Integer(1556704800123)), Some(1556704800));
+        assert_eq!(to_timestamp(&Value::Real(1556704800.7)), Some(1556704800));
+        assert_eq!(to_timestamp(&Value::Text("1556704800".to_string())), Some(1556704800));
+        assert_eq!(to_timestamp(&Value::Text(" 1556704800123 ".to_string())), Some(1556704800));
+        assert_eq!(to_timestamp(&Value::Text("1556704800.5".to_string())), Some(1556704800));
+        assert_eq!(to_timestamp(&Value::Text("2019-05-01T10:00:00+00:00".to_string())), Some(1556704800));
+        let local = Local.with_ymd_and_hms(2019, 5, 1, 10, 0, 0).unwrap().timestamp();
+        assert_eq!(to_timestamp(&Value::Text("2019-05-01 10:00:00".to_string())), Some(local));
+        assert_eq!(to_timestamp(&Value::Text("2019-05-01T10:00:00.250".to_string())), Some(local));
+    }
+
+    #[test]
+    fn rejects_unreadable_timestamps() {
+        for s in ["", "garbage", "NaN", "inf", "2019-05-01"] {
+            assert_eq!(to_timestamp(&Value::Text(s.to_string())), None, "{:?} should be invalid", s);
+        }
+        assert_eq!(to_timestamp(&Value::Null), None);
+    }
+
+    #[test]
+    fn skips_samples_recorded_before_hosts_were_stored() {
+        let path = env::temp_dir().join(format!("tracking-import-{}.db", process::id()));
+        let _ = fs::remove_file(&path);
+        {
+            let source = Connection::open(&path).unwrap();
+            source.execute("create table focus (id integer primary key, window_class text, title text, timestamp text);", ()).unwrap();
+            source.execute("insert into focus (window_class, title, timestamp) values ('vim', 'a', '100'), ('vim', 'a', '110'), (null, 'b', '120');", ()).unwrap();
+        }
+
+        // the daemon recorded the first sample too, before it stored hosts
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("create table tracking (id integer primary key, class text not null, title text not null, idle integer not null, ts integer not null unique);", ()).unwrap();
+        conn.execute("insert into tracking (class, title, idle, ts) values ('vim', 'a', 0, 100);", ()).unwrap();
+        db::create_tables(&conn).unwrap();
+
+        let args = Args::parse_from(["import", path.to_str().unwrap()]);
+        let summary = import_legacy(&conn, &args).unwrap();
+        assert_eq!((summary.imported, summary.duplicates, summary.invalid), (1, 1, 1));
+        let summary = import_legacy(&conn, &args).unwrap();
+        assert_eq!((summary.imported, summary.duplicates, summary.invalid), (0, 2, 1));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,319 @@
+use chrono::prelude::*;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::HashSet;
+use clap::Parser;
+use std::process;
+use tracking::annotations::{self, Annotation};
+use tracking::db::{self, Sample};
+use tracking::edits::{self, Edit};
+use tracking::runs::{self, Run};
+use tracking::spans::Span;
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to merge into, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(long, help = "Host to record samples for that were stored without one, required for databases created before hosts were stored")]
+    host: Option<String>,
+
+    #[arg(required = true, help = "The databases to merge")]
+    sources: Vec<String>,
+}
+
+
+/// Reads all samples of `path` without changing it, which may be an older
+/// database that does not store hosts yet. Encrypted sources need to use the
+/// same key as the target.
+fn read_samples(path: &str, default_host: &str) -> rusqlite::Result<Vec<Sample>> {
+    let conn = db::connect(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let has_host = conn.prepare("select * from tracking limit 0;")?.column_names().contains(&"host");
+    let query = if has_host {
+        "select class, title, idle, ts, host from tracking order by ts asc;"
+    } else {
+        "select class, title, idle, ts, '' from tracking order by ts asc;"
+    };
+    let mut stmt = conn.prepare(query)?;
+    let rows = stmt.query_map((), |row| {
+        let host: String = row.get(4)?;
+        Ok(Sample {
+            class: row.get(0)?,
+            title: row.get(1)?,
+            idle:  row.get::<_, i64>(2)? == 1,
+            ts:    row.get(3)?,
+            host:  if host.is_empty() { default_host.to_string() } else { host },
+        })
+    })?;
+    rows.collect()
+}
+
+/// Spans `compact` merged the older samples of `path` into, hosts are
+/// filled in like for the samples.
+fn read_compacted(path: &str, default_host: &str) -> rusqlite::Result<Vec<Span>> {
+    let conn = db::connect(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let has_compacted: bool = conn.query_row("select count(*) > 0 from sqlite_master where type = 'table' and name = 'compacted';", (), |row| row.get(0))?;
+    if !has_compacted {
+        return Ok(vec![]);
+    }
+    let filter = db::Filter { start: i64::MIN, end: i64::MAX, idle: true, host: None };
+    let mut spans = db::compacted(&conn, &filter)?;
+    for s in spans.iter_mut().filter(|s| s.host.is_empty()) {
+        s.host = default_host.to_string();
+    }
+    Ok(spans)
+}
+
+/// Manual edits of `path`, hosts are filled in like for the samples.
+fn read_edits(path: &str, default_host: &str) -> rusqlite::Result<Vec<Edit>> {
+    let conn = db::connect(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let has_edits: bool = conn.query_row("select count(*) > 0 from sqlite_master where type = 'table' and name = 'edits';", (), |row| row.get(0))?;
+    if !has_edits {
+        return Ok(vec![]);
+    }
+    let mut edits = edits::load(&conn, i64::MIN, i64::MAX, &None)?;
+    for e in edits.iter_mut().filter(|e| e.host.is_empty()) {
+        e.host = default_host.to_string();
+    }
+    Ok(edits)
+}
+
+/// Tagged ranges of `path`.
+fn read_annotations(path: &str) -> rusqlite::Result<Vec<Annotation>> {
+    let conn = db::connect(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let has_annotations: bool = conn.query_row("select count(*) > 0 from sqlite_master where type = 'table' and name = 'annotations';", (), |row| row.get(0))?;
+    if !has_annotations {
+        return Ok(vec![]);
+    }
+    annotations::load(&conn, i64::MIN, i64::MAX)
+}
+
+/// Times the daemon of `path` was running, hosts are filled in like for the
+/// samples although runs were always recorded with one.
+fn read_runs(path: &str, default_host: &str) -> rusqlite::Result<Vec<Run>> {
+    let conn = db::connect(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let has_runs: bool = conn.query_row("select count(*) > 0 from sqlite_master where type = 'table' and name = 'runs';", (), |row| row.get(0))?;
+    if !has_runs {
+        return Ok(vec![]);
+    }
+    let mut runs = runs::load(&conn, i64::MIN, i64::MAX, &None)?;
+    for r in runs.iter_mut().filter(|r| r.host.is_empty()) {
+        r.host = default_host.to_string();
+    }
+    Ok(runs)
+}
+
+/// The host to record samples of `path` for that were stored without one.
+/// Sources created before hosts were stored need `host` since the name of the
+/// file tells nothing about the machine, synced copies are often all called
+/// `tracking.db`.
+fn default_host(path: &str, host: &Option<String>) -> Result<String, String> {
+    let hostless = || -> rusqlite::Result<bool> {
+        let conn = db::connect(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let has_host = conn.prepare("select * from tracking limit 0;")?.column_names().contains(&"host");
+        Ok(!has_host || conn.query_row("select count(*) > 0 from tracking where host = '';", (), |row| row.get(0))?)
+    };
+    match host {
+        Some(h) => Ok(h.clone()),
+        None if hostless().map_err(|e| format!("Could not read {}: {}", path, e))? => {
+            Err(format!("{} has samples without host, give the machine they were recorded on with --host", path))
+        },
+        None => Ok(String::new()),
+    }
+}
+
+fn date(ts: i64) -> NaiveDate {
+    Local.timestamp_opt(ts, 0).unwrap().date_naive()
+}
+
+/// How many rows of each kind were merged out of the ones of the source.
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    samples: (usize, usize),
+    compacted: (usize, usize),
+    edits: (usize, usize),
+    annotations: (usize, usize),
+    runs: (usize, usize),
+}
+
+/// Copies everything of `source` into `conn` that isn't there yet, samples are
+/// unique per host and time, so merging the same data twice changes nothing.
+fn merge(conn: &mut Connection, source: &str, default_host: &str) -> rusqlite::Result<Summary> {
+    let samples = read_samples(source, default_host)?;
+    let source_compacted = read_compacted(source, default_host)?;
+    let source_edits = read_edits(source, default_host)?;
+    let source_annotations = read_annotations(source)?;
+    let source_runs = read_runs(source, default_host)?;
+
+    let tx = conn.transaction()?;
+    let mut merged_compacted = 0;
+    for span in &source_compacted {
+        if db::insert_compacted_new(&tx, span)? {
+            merged_compacted += 1;
+        }
+    }
+    // compact replaces whole days and moves the time of hour and day
+    // resolutions within them, so the ranges of the spans don't tell which
+    // samples they hold, samples of days a host has compacted spans on were
+    // compacted already
+    let compacted_days: HashSet<(String, NaiveDate)> = db::compacted(&tx, &db::Filter { start: i64::MIN, end: i64::MAX, idle: true, host: None })?
+        .into_iter().map(|s| (s.host, date(s.start))).collect();
+    let mut merged = 0;
+    for sample in &samples {
+        if compacted_days.contains(&(sample.host.clone(), date(sample.ts))) {
+            continue;
+        }
+        if db::insert_new(&tx, sample)? {
+            merged += 1;
+        }
+    }
+    let known = edits::load(&tx, i64::MIN, i64::MAX, &None)?;
+    let mut merged_edits = 0;
+    for e in &source_edits {
+        let same = |k: &Edit| (k.kind, k.start, k.end, &k.class, &k.title, &k.host) == (e.kind, e.start, e.end, &e.class, &e.title, &e.host);
+        if !known.iter().any(same) {
+            edits::insert(&tx, e)?;
+            merged_edits += 1;
+        }
+    }
+    let known = annotations::load(&tx, i64::MIN, i64::MAX)?;
+    let mut merged_annotations = 0;
+    for a in &source_annotations {
+        if !known.iter().any(|k| (k.start, k.end, &k.tag, &k.note) == (a.start, a.end, &a.tag, &a.note)) {
+            annotations::insert(&tx, a)?;
+            merged_annotations += 1;
+        }
+    }
+    let mut merged_runs = 0;
+    for run in &source_runs {
+        if runs::insert(&tx, run)? {
+            merged_runs += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(Summary {
+        samples: (merged, samples.len()),
+        compacted: (merged_compacted, source_compacted.len()),
+        edits: (merged_edits, source_edits.len()),
+        annotations: (merged_annotations, source_annotations.len()),
+        runs: (merged_runs, source_runs.len()),
+    })
+}
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+
+    let mut conn = db::open(&db).unwrap();
+    for source in &args.sources {
+        let summary = default_host(source, &args.host)
+            .and_then(|host| merge(&mut conn, source, &host).map_err(|e| format!("Could not merge {}: {}", source, e)))
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            });
+        let (merged, total) = summary.samples;
+        println!("{}: merged {} samples, skipped {} already present", source, merged, total - merged);
+        for (name, (merged, total)) in [("compacted spans", summary.compacted), ("edits", summary.edits), ("tags", summary.annotations), ("runs", summary.runs)] {
+            if total > 0 {
+                println!("{}: merged {} {}, skipped {} already present", source, merged, name, total - merged);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use tracking::edits::Kind;
+
+    const DAY: i64 = 24 * 3600;
+
+    fn temp_path(name: &str) -> String {
+        let path = env::temp_dir().join(format!("tracking-merge-{}-{}.db", name, process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    fn sample(host: &str, ts: i64) -> Sample {
+        Sample { class: "vim".to_string(), title: String::new(), idle: false, ts, host: host.to_string() }
+    }
+
+    fn target() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::create_tables(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn merging_twice_changes_nothing() {
+        let path = temp_path("twice");
+        {
+            let conn = db::open(&path).unwrap();
+            db::insert_new(&conn, &sample("a", 10 * DAY)).unwrap();
+            db::insert_new(&conn, &sample("a", 10 * DAY + 10)).unwrap();
+            let span = Span { class: "vim".to_string(), title: String::new(), idle: false, host: "a".to_string(), start: 0, end: 3600 };
+            db::insert_compacted(&conn, &span).unwrap();
+            let edit = Edit { id: 0, kind: Kind::Relabel, start: 0, end: 60, class: "xterm".to_string(), title: String::new(), host: String::new() };
+            edits::insert(&conn, &edit).unwrap();
+            annotations::insert(&conn, &Annotation { id: 0, start: 0, end: 60, tag: "work".to_string(), note: String::new() }).unwrap();
+            runs::insert(&conn, &Run { id: 0, host: "a".to_string(), start: 10 * DAY, last_seen: 10 * DAY + 10, stopped: None }).unwrap();
+        }
+
+        let mut conn = target();
+        let host = default_host(&path, &None).unwrap();
+        let first = merge(&mut conn, &path, &host).unwrap();
+        assert_eq!(first, Summary { samples: (2, 2), compacted: (1, 1), edits: (1, 1), annotations: (1, 1), runs: (1, 1) });
+        let second = merge(&mut conn, &path, &host).unwrap();
+        assert_eq!(second, Summary { samples: (0, 2), compacted: (0, 1), edits: (0, 1), annotations: (0, 1), runs: (0, 1) });
+
+        let filter = db::Filter { start: i64::MIN, end: i64::MAX, idle: true, host: None };
+        assert_eq!(db::raw_samples(&conn, &filter).unwrap().len(), 2);
+        assert_eq!(db::compacted(&conn, &filter).unwrap().len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn samples_of_compacted_days_are_skipped() {
+        let path = temp_path("compacted");
+        let noon = 10 * DAY + 12 * 3600;
+        {
+            let conn = db::open(&path).unwrap();
+            for s in [sample("a", noon + 60), sample("a", noon + DAY), sample("b", noon + 60)] {
+                db::insert_new(&conn, &s).unwrap();
+            }
+        }
+
+        // the target compacted the day of host a, its samples are in the span
+        let mut conn = target();
+        let span = Span { class: "vim".to_string(), title: String::new(), idle: false, host: "a".to_string(), start: noon, end: noon + 3600 };
+        db::insert_compacted(&conn, &span).unwrap();
+        assert_eq!(merge(&mut conn, &path, "").unwrap().samples, (2, 3));
+
+        let filter = db::Filter { start: i64::MIN, end: i64::MAX, idle: true, host: None };
+        let times: Vec<(String, i64)> = db::raw_samples(&conn, &filter).unwrap().into_iter().map(|s| (s.host, s.ts)).collect();
+        assert_eq!(times, vec![("b".to_string(), noon + 60), ("a".to_string(), noon + DAY)]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sources_without_hosts_need_one() {
+        let path = temp_path("hostless");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute("create table tracking (id integer primary key, class text not null, title text not null, idle integer not null, ts integer not null unique);", ()).unwrap();
+            conn.execute("insert into tracking (class, title, idle, ts) values ('vim', '', 0, 0);", ()).unwrap();
+        }
+        assert!(default_host(&path, &None).is_err());
+
+        let mut conn = target();
+        let host = default_host(&path, &Some("laptop".to_string())).unwrap();
+        assert_eq!(merge(&mut conn, &path, &host).unwrap().samples, (1, 1));
+        let filter = db::Filter { start: i64::MIN, end: i64::MAX, idle: true, host: Some("laptop".to_string()) };
+        assert_eq!(db::raw_samples(&conn, &filter).unwrap().len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,351 @@
+use chrono::prelude::*;
+use chrono::Months;
+use clap::{Parser, ValueEnum, ArgAction};
+use std::collections::HashMap;
+use std::process;
+use tracking::annotations;
+use tracking::config::{self, Config};
+use tracking::day::{parse_date, Day};
+use tracking::focus;
+use tracking::db::{self, Sample};
+use tracking::runs;
+use tracking::spans::{self, Span};
+use tracking::switches;
+use tracking::table::{fmt_duration, Table};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Group {
+    Class,
+    Category,
+    Host,
+    Tag,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Text,
+    Markdown,
+}
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to connect to, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(short, long, help = "The config file, defaults to ~/.config/tracking/config.json can also be set with TRACKING_CONFIG environment variable")]
+    config: Option<String>,
+
+    #[arg(short, long, help = "The start date in the format YYYY-MM-DD, defaults to today")]
+    start: Option<String>,
+
+    #[arg(short, long, help = "The end date in the format YYYY-MM-DD, defaults to today")]
+    end: Option<String>,
+
+    #[arg(short, long, help = "Period to summarize, the range is extended to whole periods", value_enum, default_value_t = Period::Day)]
+    period: Period,
+
+    #[arg(short, long, help = "Summarize per window class, per category from the config file, per host or per tag", value_enum, default_value_t = Group::Class)]
+    by: Group,
+
+    #[arg(long, help = "Only include samples recorded on this host, all hosts are summed up by default")]
+    host: Option<String>,
+
+    #[arg(long, help = "Only include time within ranges tagged with this tag")]
+    tag: Option<String>,
+
+    #[arg(short, long, help = "Output format", value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    #[arg(short = 'n', long, help = "Only list the top N classes or categories of each period")]
+    top: Option<usize>,
+
+    #[arg(short, long, help = "Include idle time in report", action = ArgAction::SetTrue)]
+    idle: Option<bool>,
+
+    #[arg(long, help = "Show context switches, focus spans, deep work and the time to return after switching to chat", action = ArgAction::SetTrue)]
+    switches: Option<bool>,
+
+    #[arg(long, help = "Number of deep work blocks listed with --switches", default_value_t = 3)]
+    deep_work: usize,
+}
+
+
+#[derive(Debug, Default, Clone)]
+struct Stats {
+    total: i64,
+    first: Option<i64>,
+    last: i64,
+    longest: i64,
+}
+
+impl Stats {
+    fn add(&mut self, span: &Span) {
+        self.total += span.seconds();
+        self.first = Some(self.first.map_or(span.start, |f| f.min(span.start)));
+        self.last = self.last.max(span.end);
+        self.longest = self.longest.max(span.seconds());
+    }
+}
+
+
+fn period_start(date: NaiveDate, period: Period) -> NaiveDate {
+    match period {
+        Period::Day => date,
+        Period::Week => date.week(Weekday::Mon).first_day(),
+        Period::Month => date.with_day(1).unwrap(),
+    }
+}
+
+fn period_next(date: NaiveDate, period: Period) -> NaiveDate {
+    match period {
+        Period::Day => date.succ_opt().unwrap(),
+        Period::Week => date.checked_add_days(chrono::Days::new(7)).unwrap(),
+        Period::Month => date.checked_add_months(Months::new(1)).unwrap(),
+    }
+}
+
+fn period_title(date: NaiveDate, period: Period) -> String {
+    match period {
+        Period::Day => format!("{}", date.format("%a, %Y-%m-%d")),
+        Period::Week => format!("Week {} ({} - {})", date.format("%G-W%V"), date, period_next(date, period).pred_opt().unwrap()),
+        Period::Month => format!("{}", date.format("%B %Y")),
+    }
+}
+
+/// Name of the group of time that isn't tagged.
+const UNTAGGED: &str = "untagged";
+
+fn trend(total: i64, previous: i64) -> String {
+    if previous == 0 {
+        return if total == 0 { "".to_string() } else { "new".to_string() };
+    }
+    format!("{:+.0}%", 100.0 * (total - previous) as f64 / previous as f64)
+}
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+    let config = Config::load(&config::path(args.config)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    let today = Local::now().date_naive();
+    let start = args.start.map(|s| parse_date(&s).expect("Start date must be of format YYYY-MM-DD")).unwrap_or(today);
+    let end = args.end.map(|e| parse_date(&e).expect("End date must be of format YYYY-MM-DD")).unwrap_or(today);
+
+    // the period before the first one is only loaded to calculate the trend
+    let first = period_start(start, args.period);
+    let mut periods = vec![];
+    let mut cur = first;
+    while cur <= end {
+        periods.push(cur);
+        cur = period_next(cur, args.period);
+    }
+    let previous = match args.period {
+        Period::Day => first.pred_opt().unwrap(),
+        Period::Week => first.checked_sub_days(chrono::Days::new(7)).unwrap(),
+        Period::Month => first.checked_sub_months(Months::new(1)).unwrap(),
+    };
+    let filter = db::Filter {
+        start: Day::new(&Local, previous).start.timestamp(),
+        end: Day::new(&Local, cur).start.timestamp(),
+        idle: args.idle.is_some() && args.idle.unwrap(),
+        host: args.host,
+    };
+
+    let conn = db::open_read_only(&db).unwrap_or_else(|e| {
+        eprintln!("Could not open {}: {}", db, e);
+        process::exit(1);
+    });
+    let mut samples = db::samples(&conn, &filter).unwrap();
+    let tags = annotations::load(&conn, filter.start, filter.end).unwrap();
+    let sessions = focus::load(&conn, filter.start, filter.end, &filter.host).unwrap();
+    // only times after the daemon recorded its first run tell idle and no data apart
+    let now = Local::now().timestamp();
+    let first_run = runs::first(&conn, &filter.host).unwrap();
+    let gaps = runs::no_data(&conn, filter.start, filter.end, now, &filter.host).unwrap();
+    let all_runs = runs::load(&conn, filter.start, filter.end, &filter.host).unwrap();
+    let mut hosts: Vec<&str> = all_runs.iter().map(|r| r.host.as_str()).collect();
+    hosts.sort();
+    hosts.dedup();
+    if let Some(tag) = &args.tag {
+        samples = annotations::tagged(samples, &tags, tag);
+    }
+
+    let group = |s: &Sample| -> String {
+        match args.by {
+            Group::Class => s.class.clone(),
+            Group::Category => config.category(&s.class).to_string(),
+            Group::Host => s.host.clone(),
+            Group::Tag => annotations::tag_at(&tags, s.ts).unwrap_or(UNTAGGED).to_string(),
+        }
+    };
+    let grouped = spans::merge_by(&samples, |a, b| group(a) == group(b));
+    let grouped: Vec<Span> = grouped.into_iter().map(|mut s| {
+        s.class = match args.by {
+            Group::Class => s.class,
+            Group::Category => config.category(&s.class).to_string(),
+            Group::Host if s.host.is_empty() => "unknown".to_string(),
+            Group::Host => s.host.clone(),
+            Group::Tag => annotations::tag_at(&tags, s.start).unwrap_or(UNTAGGED).to_string(),
+        };
+        s
+    }).collect();
+    let grouped = spans::union(&grouped, |a, b| a.class == b.class);
+    // any activity regardless of class, used for the idle time of each host
+    let active = spans::merge_by(&samples, |_, _| true);
+    // and of all hosts together, used for the totals and active days since
+    // the time of hosts used at the same time only passed once
+    let active_any = spans::union(&active, |_, _| true);
+
+    let mut previous_stats: HashMap<String, Stats> = HashMap::new();
+    let mut previous_total = Stats::default();
+    let mut output = String::new();
+    for p in std::iter::once(previous).chain(periods.iter().copied()) {
+        let p_start = Day::new(&Local, p).start.timestamp();
+        let p_end = Day::new(&Local, period_next(p, args.period)).start.timestamp();
+
+        let mut stats: HashMap<String, Stats> = HashMap::new();
+        for span in spans::clip(&grouped, p_start, p_end) {
+            stats.entry(span.class.clone()).or_default().add(&span);
+        }
+        let mut total = Stats::default();
+        let mut active_days = vec![];
+        for span in spans::clip(&active_any, p_start, p_end) {
+            total.add(&span);
+            let date = Local.timestamp_opt(span.start, 0).unwrap().date_naive();
+            if !active_days.contains(&date) {
+                active_days.push(date);
+            }
+        }
+
+        if p != previous {
+            let mut sorted: Vec<(&String, &Stats)> = stats.iter().collect();
+            sorted.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(b.0)));
+            if let Some(n) = args.top {
+                sorted.truncate(n);
+            }
+
+            let time_format = if args.period == Period::Day { "%H:%M" } else { "%a %d. %H:%M" };
+            let time = |ts: i64| format!("{}", Local.timestamp_opt(ts, 0).unwrap().format(time_format));
+            let days = active_days.len().max(1) as i64;
+            let row = |name: &str, s: &Stats, prev: i64| vec![
+                name.to_string(),
+                fmt_duration(s.total),
+                fmt_duration(s.total / days),
+                trend(s.total, prev),
+                s.first.map(time).unwrap_or_default(),
+                if s.first.is_some() { time(s.last) } else { "".to_string() },
+                fmt_duration(s.longest),
+            ];
+
+            let name = match args.by {
+                Group::Class => "Class",
+                Group::Category => "Category",
+                Group::Host => "Host",
+                Group::Tag => "Tag",
+            };
+            let mut table = Table::new(&[name, "Total", "Avg/day", "Trend", "First", "Last", "Longest"]);
+            for (name, s) in sorted {
+                table.push(row(name, s, previous_stats.get(name).map_or(0, |p| p.total)));
+            }
+            table.push(row("Total", &total, previous_total.total));
+            let (r_start, r_end) = (p_start.max(first_run.unwrap_or(i64::MAX)), p_end.min(now));
+            if args.tag.is_none() && r_start < r_end {
+                let no_data: i64 = runs::clip(&gaps, r_start, r_end).iter().map(|g| g.1 - g.0).sum();
+                // hosts are active at the same time, so the idle time is
+                // what the runs of each host cover minus its own activity
+                // during them, time added while it wasn't running is no idle
+                let idle: i64 = hosts.iter().map(|host| {
+                    let host_runs: Vec<&runs::Run> = all_runs.iter().filter(|r| r.host == *host).collect();
+                    let host_active: Vec<Span> = active.iter().filter(|s| s.host == *host).cloned().collect();
+                    host_runs.iter().map(|r| {
+                        let (start, end) = (r.start.max(r_start), r.end().min(r_end));
+                        let active: i64 = spans::clip(&host_active, start, end).iter().map(|s| s.seconds()).sum();
+                        (end - start - active).max(0)
+                    }).sum::<i64>()
+                }).sum();
+                for (name, secs) in [("Idle", idle), ("No data", no_data)] {
+                    table.push(vec![name.to_string(), fmt_duration(secs), fmt_duration(secs / days), "".to_string(), "".to_string(), "".to_string(), "".to_string()]);
+                }
+            }
+
+            let title = period_title(p, args.period);
+            match args.format {
+                Format::Text => {
+                    output += &format!("{}\n\n{}\n", title, table.render_text());
+                },
+                Format::Markdown => {
+                    output += &format!("## {}\n\n{}\n", title, table.render_markdown());
+                },
+            }
+
+            let mut table = Table::new(&["Focus session", "Length", "Focused", "Distracted", "Distractions", "Most used"]);
+            for session in sessions.iter().filter(|s| s.start >= p_start && s.start < p_end) {
+                let summary = &session.summary;
+                table.push(vec![
+                    format!("{} {}", time(session.start), session.label).trim_end().to_string(),
+                    fmt_duration(session.ended.unwrap_or(session.end) - session.start),
+                    fmt_duration(summary.focused),
+                    fmt_duration(summary.distracted),
+                    summary.distractions.to_string(),
+                    summary.classes.first().map(|c| c.0.clone()).unwrap_or_default(),
+                ]);
+            }
+            if !table.rows.is_empty() {
+                match args.format {
+                    Format::Text => output += &format!("{}\n", table.render_text()),
+                    Format::Markdown => output += &format!("{}\n", table.render_markdown()),
+                }
+            }
+
+            if args.switches.is_some() && args.switches.unwrap() {
+                let in_period: Vec<Sample> = samples.iter().filter(|s| s.ts >= p_start && s.ts < p_end).cloned().collect();
+                let analysis = switches::analyze(&in_period, &config);
+                let mut table = Table::new(&["Switches", "Per hour", "Median span", "Back from chat", "Median return", "Longest return"]);
+                table.push(vec![
+                    analysis.switches.len().to_string(),
+                    format!("{:.1}", analysis.switches_per_hour()),
+                    fmt_duration(analysis.median_span()),
+                    analysis.returns.len().to_string(),
+                    fmt_duration(analysis.median_return()),
+                    fmt_duration(analysis.returns.iter().copied().max().unwrap_or_default()),
+                ]);
+                let mut deep_work = Table::new(&["Deep work", "Until", "Length", "Classes"]);
+                for block in analysis.deep_work.iter().take(args.deep_work) {
+                    let mut classes: Vec<&str> = vec![];
+                    for s in analysis.spans.iter().filter(|s| s.host == block.host && s.start >= block.start && s.start < block.end) {
+                        if !classes.contains(&s.class.as_str()) {
+                            classes.push(&s.class);
+                        }
+                    }
+                    deep_work.push(vec![time(block.start), time(block.end), fmt_duration(block.seconds()), classes.join(", ")]);
+                }
+                match args.format {
+                    Format::Text => output += &format!("{}\n", table.render_text()),
+                    Format::Markdown => output += &format!("{}\n", table.render_markdown()),
+                }
+                if !deep_work.rows.is_empty() {
+                    match args.format {
+                        Format::Text => output += &format!("{}\n", deep_work.render_text()),
+                        Format::Markdown => output += &format!("{}\n", deep_work.render_markdown()),
+                    }
+                }
+            }
+        }
+        previous_stats = stats;
+        previous_total = total;
+    }
+    print!("{}", output);
+}
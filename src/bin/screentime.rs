@@ -1,14 +1,16 @@
-use rusqlite::Connection;
 use chrono::prelude::*;
-use chrono::naive::Days;
-use chrono::{Datelike, Local, Duration};
-use std::env;
+use chrono::{Local, Duration};
 use clap::Parser;
+use std::process;
+use tracking::annotations;
+use tracking::day;
+use tracking::db;
+use tracking::spans;
 
 #[derive(Debug)]
 struct Res {
     class: String,
-    count: i64,
+    secs: i64,
 }
 
 
@@ -19,7 +21,13 @@ struct Args {
     database: Option<String>,
 
     #[arg(short, long, help = "The start time which constitutes a new day, defaults to 0:00")]
-    start: Option<String>
+    start: Option<String>,
+
+    #[arg(long, help = "Only include samples recorded on this host, all hosts are included by default")]
+    host: Option<String>,
+
+    #[arg(long, help = "Only include time within ranges tagged with this tag")]
+    tag: Option<String>,
 
 }
 
@@ -35,21 +43,12 @@ fn fmt(ts: Duration) -> String {
 }
 
 fn main() {
-    let mut db = if let Ok(s) = env::var("TRACKING_DB") {
-        s
-    } else {
-        "tracking.db".to_string()
-    };
-
     let args = Args::parse();
-    if args.database.is_some() {
-        db = args.database.unwrap();
-    }
+    let db = db::path(args.database);
 
     let mut start_hour = 0;
     let mut start_minute = 0;
-    if args.start.is_some() {
-        let start = args.start.unwrap();
+    if let Some(start) = args.start {
         let parts: Vec<_> = start.split(':').collect();
         if parts.len() != 2 {
             println!("Start time must be of format: HH:MM");
@@ -62,38 +61,52 @@ fn main() {
 
 
 
-    let conn = Connection::open(&db).unwrap();
+    let conn = db::open_read_only(&db).unwrap_or_else(|e| {
+        eprintln!("Could not open {}: {}", db, e);
+        process::exit(1);
+    });
+    let Some(start_time) = NaiveTime::from_hms_opt(start_hour, start_minute, 0) else {
+        println!("Start time must be of format: HH:MM");
+        return;
+    };
     let now = Local::now();
-    let mut zero_hour = Local.with_ymd_and_hms(now.year(), now.month(), now.day(), start_hour, start_minute, 0).unwrap();
-    if zero_hour > now {
-        zero_hour = zero_hour.checked_sub_days(Days::new(1)).unwrap();
+    // the start time doesn't exist or repeats on days the clocks are changed
+    let mut date = now.date_naive();
+    if day::resolve_local(&Local, date.and_time(start_time)) > now {
+        date = date.pred_opt().unwrap();
+    }
+    let zero_hour = day::resolve_local(&Local, date.and_time(start_time));
+    let twenty_fourth_hour = day::resolve_local(&Local, date.succ_opt().unwrap().and_time(start_time));
+    let filter = db::Filter {
+        start: zero_hour.timestamp(),
+        end: twenty_fourth_hour.timestamp(),
+        idle: false,
+        host: args.host,
+    };
+    let mut samples = db::samples(&conn, &filter).unwrap();
+    if let Some(tag) = &args.tag {
+        samples = annotations::tagged(samples, &annotations::load(&conn, filter.start, filter.end).unwrap(), tag);
     }
-    let twenty_fourth_hour = zero_hour.checked_add_days(Days::new(1)).unwrap();
-    let query = &format!("select class, count (*) FROM tracking where ts > {} and ts < {} and idle not like 1 group by class order by count (*) desc;", zero_hour.timestamp(), twenty_fourth_hour.timestamp());
-    let mut stmt = conn.prepare(query).unwrap();
-
-    let res = stmt.query_map((), |row| {
-        Ok(Res {
-            class: row.get(0)?,
-            count: row.get(1)?,
-        })
-    }).unwrap();
-
-
-    let mut counts = vec![];
-    let mut count = 0;
-    for r in res {
-        let c = r.unwrap();
-        count += c.count;
-        counts.push(c);
+
+    // time of machines used at the same time counts once, like in report
+    let classes = spans::union(&spans::merge_by(&samples, |a, b| a.class == b.class), |a, b| a.class == b.class);
+    let mut counts: Vec<Res> = vec![];
+    for span in spans::clip(&classes, filter.start, filter.end) {
+        match counts.iter_mut().find(|c| c.class == span.class) {
+            Some(c) => c.secs += span.seconds(),
+            None => counts.push(Res { class: span.class.clone(), secs: span.seconds() }),
+        }
     }
+    counts.sort_by_key(|c| std::cmp::Reverse(c.secs));
+    let active = spans::union(&spans::merge_by(&samples, |_, _| true), |_, _| true);
+    let total: i64 = spans::clip(&active, filter.start, filter.end).iter().map(|s| s.seconds()).sum();
 
     let nb = min(counts.len(), 3);
-    let mut output = fmt(Duration::seconds(count * 10));
-    for i in 0..nb {
-        output += counts[i].class.as_str();
+    let mut output = fmt(Duration::seconds(total));
+    for c in counts.iter().take(nb) {
+        output += c.class.as_str();
         output += ": ";
-        output += &fmt(Duration::seconds(counts[i].count * 10));
+        output += &fmt(Duration::seconds(c.secs));
     }
     println!("{}", output);
 }
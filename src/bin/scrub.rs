@@ -0,0 +1,98 @@
+use clap::{Parser, ArgAction};
+use std::process;
+use tracking::config::{self, Config};
+use tracking::db;
+use tracking::edits::{self, Kind};
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to scrub, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(short, long, help = "The config file, defaults to ~/.config/tracking/config.json can also be set with TRACKING_CONFIG environment variable")]
+    config: Option<String>,
+
+    #[arg(short = 'n', long, help = "Only print what would be changed", action = ArgAction::SetTrue)]
+    dry_run: Option<bool>,
+}
+
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+    let config = Config::load(&config::path(args.config)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    if config.privacy.rules.is_empty() {
+        eprintln!("No privacy rules configured, nothing to do");
+        return;
+    }
+    let dry_run = args.dry_run.is_some() && args.dry_run.unwrap();
+
+    let mut conn = db::open(&db).unwrap();
+    let tx = conn.transaction().unwrap();
+    let mut changed = 0;
+    let mut removed = 0;
+    for table in ["tracking", "compacted"] {
+        let windows: Vec<(String, String, i64)> = {
+            let mut stmt = tx.prepare(&format!("select class, title, count(*) from {} group by class, title;", table)).unwrap();
+            let rows = stmt.query_map((), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+            rows.map(|r| r.unwrap()).collect()
+        };
+
+        for (class, title, count) in windows {
+            match config.privacy.apply(&class, &title) {
+                Some(new) if new == title => {},
+                Some(new) => {
+                    if !dry_run {
+                        tx.execute(&format!("update {} set title = ?1 where class = ?2 and title = ?3;", table), (&new, &class, &title)).unwrap();
+                    }
+                    changed += count;
+                },
+                None => {
+                    if !dry_run {
+                        tx.execute(&format!("delete from {} where class = ?1 and title = ?2;", table), (&class, &title)).unwrap();
+                    }
+                    removed += count;
+                },
+            }
+        }
+    }
+
+    // windows added or relabeled by hand are stored with the edits, empty
+    // titles of relabels keep the recorded ones and are left alone
+    for e in edits::load(&tx, i64::MIN, i64::MAX, &None).unwrap() {
+        if !matches!(e.kind, Kind::Add | Kind::Relabel) {
+            continue;
+        }
+        match config.privacy.apply(&e.class, &e.title) {
+            Some(new) if new == e.title || e.title.is_empty() => {},
+            Some(new) => {
+                if !dry_run {
+                    tx.execute("update edits set title = ?1 where id = ?2;", (&new, e.id)).unwrap();
+                }
+                changed += 1;
+            },
+            None => {
+                if !dry_run {
+                    edits::remove(&tx, e.id).unwrap();
+                }
+                removed += 1;
+            },
+        }
+    }
+    tx.commit().unwrap();
+
+    if dry_run {
+        println!("Would change the title of {} rows and remove {} rows", changed, removed);
+        return;
+    }
+    println!("Changed the title of {} rows and removed {} rows", changed, removed);
+    // old titles stay in free pages of the file until it is rebuilt
+    if changed + removed > 0 {
+        conn.execute("vacuum;", ()).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::prelude::*;
+use clap::{Parser, ValueEnum, ArgAction};
+use std::collections::{BTreeMap, HashMap};
+use std::process;
+use tracking::config::{self, Config};
+use tracking::day::{self, parse_date};
+use tracking::db;
+use tracking::spans;
+use tracking::table::{fmt_duration, Table};
+use tracking::timesheet::{Per, Rounding};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Text,
+    Markdown,
+    Csv,
+}
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to connect to, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(short, long, help = "The config file, defaults to ~/.config/tracking/config.json can also be set with TRACKING_CONFIG environment variable")]
+    config: Option<String>,
+
+    #[arg(short, long, help = "The start date in the format YYYY-MM-DD, defaults to the first day of the current month")]
+    start: Option<String>,
+
+    #[arg(short, long, help = "The end date in the format YYYY-MM-DD, defaults to today")]
+    end: Option<String>,
+
+    #[arg(long, help = "Only create the timesheet of this client")]
+    client: Option<String>,
+
+    #[arg(long, help = "Only include samples recorded on this host, all hosts are summed up by default")]
+    host: Option<String>,
+
+    #[arg(short, long, help = "Output format", value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    #[arg(short, long, help = "Also list the time that is not assigned to any client", action = ArgAction::SetTrue)]
+    unassigned: Option<bool>,
+}
+
+
+const UNASSIGNED: &str = "unassigned";
+
+/// Time of a client and project on one day.
+#[derive(Debug, Default)]
+struct Entry {
+    actual: i64,
+    billed: i64,
+}
+
+fn hours(secs: i64) -> String {
+    format!("{:.2}", secs as f64 / 3600.0)
+}
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+    let config = Config::load(&config::path(args.config)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    if config.timesheet.clients.is_empty() {
+        eprintln!("No clients configured in the timesheet section of the config");
+        process::exit(1);
+    }
+    let date = |d: Option<String>, default: NaiveDate| d.map(|d| parse_date(&d).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    })).unwrap_or(default);
+    let today = Local::now().date_naive();
+    let first = date(args.start, today.with_day(1).unwrap());
+    let last = date(args.end, today);
+    if last < first {
+        eprintln!("The end date must not be before the start date");
+        process::exit(1);
+    }
+    let unassigned = args.unassigned.is_some() && args.unassigned.unwrap();
+
+    let days = day::days(&Local, first, last);
+    let filter = db::Filter {
+        start: days.first().unwrap().start.timestamp(),
+        end: days.last().unwrap().end.timestamp(),
+        idle: false,
+        host: args.host,
+    };
+    let conn = db::open_read_only(&db).unwrap_or_else(|e| {
+        eprintln!("Could not open {}: {}", db, e);
+        process::exit(1);
+    });
+    let samples = db::samples(&conn, &filter).unwrap();
+
+    // the rules are regexes, only match every window once
+    let mut assigned: HashMap<(&str, &str), Option<(String, String)>> = HashMap::new();
+    for s in &samples {
+        assigned.entry((&s.class, &s.title)).or_insert_with(|| {
+            match config.timesheet.assign(&s.class, &s.title) {
+                Some((client, project)) => Some((client.name.clone(), project.to_string())),
+                None if unassigned => Some((UNASSIGNED.to_string(), "".to_string())),
+                None => None,
+            }
+        });
+    }
+    let key = |class: &str, title: &str| assigned[&(class, title)].clone();
+    let blocks = spans::merge_by(&samples, |a, b| key(&a.class, &a.title) == key(&b.class, &b.title));
+    // machines used at the same time for a client don't make it bill twice
+    let blocks = spans::union(&blocks, |a, b| key(&a.class, &a.title) == key(&b.class, &b.title));
+
+    let rounding = |client: &str| -> &Rounding {
+        config.timesheet.clients.iter().find(|c| c.name == client).map(|c| &c.rounding).unwrap_or(&config.timesheet.rounding)
+    };
+    // client -> (date, project) -> time, sorted by date for the output
+    let mut sheets: BTreeMap<String, BTreeMap<(NaiveDate, String), Entry>> = BTreeMap::new();
+    for d in &days {
+        for block in spans::clip(&blocks, d.start.timestamp(), d.end.timestamp()) {
+            let Some((client, project)) = key(&block.class, &block.title) else {
+                continue;
+            };
+            if args.client.as_ref().is_some_and(|c| *c != client) {
+                continue;
+            }
+            let rounding = rounding(&client);
+            let entry = sheets.entry(client).or_default().entry((d.date, project)).or_default();
+            entry.actual += block.seconds();
+            if rounding.per == Per::Block {
+                entry.billed += rounding.round(block.seconds());
+            }
+        }
+    }
+    for (client, entries) in sheets.iter_mut() {
+        let rounding = rounding(client);
+        if rounding.per == Per::Day {
+            for entry in entries.values_mut() {
+                entry.billed = rounding.round(entry.actual);
+            }
+        }
+    }
+
+    if sheets.is_empty() {
+        eprintln!("No time assigned to any client between {} and {}", first, last);
+        return;
+    }
+    if args.format == Format::Csv {
+        let mut table = Table::new(&["Client", "Date", "Project", "Actual", "Billed", "Hours"]);
+        for (client, entries) in &sheets {
+            for ((date, project), entry) in entries {
+                table.push(vec![
+                    client.clone(), date.to_string(), project.clone(),
+                    fmt_duration(entry.actual), fmt_duration(entry.billed), hours(entry.billed),
+                ]);
+            }
+        }
+        print!("{}", table.render_csv());
+        return;
+    }
+
+    let mut output = String::new();
+    for (client, entries) in &sheets {
+        let mut table = Table::new(&["Date", "Project", "Actual", "Billed", "Hours"]);
+        for ((date, project), entry) in entries {
+            table.push(vec![
+                date.format("%a, %Y-%m-%d").to_string(), project.clone(),
+                fmt_duration(entry.actual), fmt_duration(entry.billed), hours(entry.billed),
+            ]);
+        }
+        let actual: i64 = entries.values().map(|e| e.actual).sum();
+        let billed: i64 = entries.values().map(|e| e.billed).sum();
+        table.push(vec!["Total".to_string(), "".to_string(), fmt_duration(actual), fmt_duration(billed), hours(billed)]);
+
+        match args.format {
+            Format::Markdown => output += &format!("## {}, {} to {}\n\n{}\n", client, first, last, table.render_markdown()),
+            _ => output += &format!("{}, {} to {}\n\n{}\n", client, first, last, table.render_text()),
+        }
+    }
+    print!("{}", output);
+}
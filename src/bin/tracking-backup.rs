@@ -0,0 +1,186 @@
+use rusqlite::OpenFlags;
+use rusqlite::backup::{Backup, StepResult};
+use chrono::prelude::*;
+use clap::{Parser, ArgAction};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::Duration;
+use tracking::db;
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to back up, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(long, help = "Number of most recent daily backups to keep, the newest one is always kept", default_value_t = 7)]
+    daily: usize,
+
+    #[arg(long, help = "Number of weeks for which the most recent backup is kept", default_value_t = 4)]
+    weekly: usize,
+
+    #[arg(short = 'z', long, help = "Compress the backup with gzip", action = ArgAction::SetTrue)]
+    compress: Option<bool>,
+
+    #[arg(help = "The directory to store the backups in")]
+    dir: String,
+}
+
+
+const PREFIX: &str = "tracking-";
+
+/// Copies the database with the online backup API, which is safe to use while
+/// the daemon is writing to it, and verifies the copy. Encrypted databases
+/// are copied with the same key.
+fn backup(db: &str, target: &Path) -> Result<(), String> {
+    let conn = db::connect(db, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|e| format!("Could not open {}: {}", db, e))?;
+    {
+        let mut copy = db::connect(target, OpenFlags::default()).map_err(|e| format!("Could not create {}: {}", target.display(), e))?;
+        let backup = Backup::new(&conn, &mut copy).map_err(|e| e.to_string())?;
+        // a backup restarts whenever the daemon writes in between two steps,
+        // which a large database copied in small steps might never survive,
+        // so all pages are copied at once and only retried while it's busy
+        loop {
+            match backup.step(-1).map_err(|e| format!("Backup to {} failed: {}", target.display(), e))? {
+                StepResult::Done => break,
+                StepResult::More => {},
+                _ => thread::sleep(Duration::from_millis(100)),
+            }
+        }
+    }
+
+    let copy = db::connect(target, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|e| e.to_string())?;
+    let result: String = copy.query_row("pragma integrity_check;", (), |row| row.get(0)).map_err(|e| e.to_string())?;
+    if result != "ok" {
+        return Err(format!("Integrity check of {} failed: {}", target.display(), result));
+    }
+    Ok(())
+}
+
+fn compress(source: &Path, target: &Path) -> io::Result<()> {
+    let mut input = File::open(source)?;
+    let mut encoder = GzEncoder::new(File::create(target)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(source)
+}
+
+/// Date of a backup file created by this program.
+fn backup_date(path: &Path) -> Option<NaiveDate> {
+    let name = path.file_name()?.to_str()?;
+    let date = name.strip_prefix(PREFIX)?.split('.').next()?;
+    if !(name.ends_with(".db") || name.ends_with(".db.gz")) {
+        return None;
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// Removes all backups except for the `daily` most recent ones and the most
+/// recent one of each of the last `weekly` weeks, the newest backup is always
+/// kept.
+fn rotate(dir: &Path, daily: usize, weekly: usize) -> io::Result<()> {
+    let mut backups: Vec<(NaiveDate, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| backup_date(&e.path()).map(|d| (d, e.path())))
+        .collect();
+    backups.sort_by(|a, b| b.cmp(a));
+
+    let mut keep: HashSet<PathBuf> = backups.iter().take(daily.max(1)).map(|(_, p)| p.clone()).collect();
+    let mut weeks = HashSet::new();
+    for (date, path) in &backups {
+        if weeks.len() >= weekly {
+            break;
+        }
+        if weeks.insert(date.iso_week()) {
+            keep.insert(path.clone());
+        }
+    }
+
+    for (_, path) in backups {
+        if !keep.contains(&path) {
+            eprintln!("Removing old backup {}", path.display());
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+    let dir = Path::new(&args.dir);
+    fs::create_dir_all(dir).unwrap_or_else(|e| panic!("Could not create {}: {}", dir.display(), e));
+
+    // write to a temporary name first, so a failed backup never looks like a valid one
+    let name = format!("{}{}.db", PREFIX, Local::now().format("%Y-%m-%d"));
+    let tmp = dir.join(format!("{}.tmp", name));
+    if let Err(e) = backup(&db, &tmp) {
+        eprintln!("{}", e);
+        fs::remove_file(&tmp).ok();
+        process::exit(1);
+    }
+
+    let target = if args.compress.is_some() && args.compress.unwrap() {
+        let target = dir.join(format!("{}.gz", name));
+        let tmp_gz = dir.join(format!("{}.gz.tmp", name));
+        compress(&tmp, &tmp_gz).unwrap_or_else(|e| panic!("Could not compress {}: {}", tmp.display(), e));
+        fs::rename(&tmp_gz, &target).unwrap();
+        target
+    } else {
+        let target = dir.join(&name);
+        fs::rename(&tmp, &target).unwrap();
+        target
+    };
+    println!("Backed up {} to {}", db, target.display());
+
+    rotate(dir, args.daily, args.weekly).unwrap_or_else(|e| panic!("Could not rotate backups in {}: {}", dir.display(), e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn backups(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    fn with_backups(name: &str, dates: &[&str], test: impl Fn(&Path)) {
+        let dir = env::temp_dir().join(format!("tracking-backup-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for date in dates {
+            File::create(dir.join(format!("{}{}.db", PREFIX, date))).unwrap();
+        }
+        File::create(dir.join("notes.txt")).unwrap();
+        test(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_recent_days_and_last_of_each_week() {
+        // 2024-01-01 is a Monday
+        let dates = ["2023-12-24", "2023-12-30", "2023-12-31", "2024-01-05", "2024-01-06", "2024-01-07"];
+        with_backups("weeks", &dates, |dir| {
+            rotate(dir, 2, 2).unwrap();
+            assert_eq!(backups(dir), vec!["notes.txt", "tracking-2023-12-31.db", "tracking-2024-01-06.db", "tracking-2024-01-07.db"]);
+        });
+    }
+
+    #[test]
+    fn always_keeps_newest_backup() {
+        with_backups("newest", &["2024-01-05", "2024-01-06"], |dir| {
+            rotate(dir, 0, 0).unwrap();
+            assert_eq!(backups(dir), vec!["notes.txt", "tracking-2024-01-06.db"]);
+        });
+    }
+}
@@ -0,0 +1,367 @@
+use chrono::prelude::*;
+use chrono::Days;
+use clap::{Parser, ArgAction};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span as Text};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::process;
+use tracking::day::{self, Day};
+use tracking::db;
+use tracking::spans::{self, Span};
+use tracking::table::fmt_duration;
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to connect to, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(short, long, help = "The date to show in the format YYYY-MM-DD, defaults to today")]
+    start: Option<String>,
+
+    #[arg(long, help = "Only include samples recorded on this host, all hosts are included by default")]
+    host: Option<String>,
+
+    #[arg(short, long, help = "Include idle time", action = ArgAction::SetTrue)]
+    idle: Option<bool>,
+}
+
+
+const COLORS: [Color; 12] = [
+    Color::Red, Color::Green, Color::Yellow, Color::Blue, Color::Magenta, Color::Cyan,
+    Color::LightRed, Color::LightGreen, Color::LightYellow, Color::LightBlue, Color::LightMagenta, Color::LightCyan,
+];
+
+/// A stable color for every class, so it doesn't change while browsing.
+fn color(class: &str) -> Color {
+    let hash = class.bytes().fold(0usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+    COLORS[hash % COLORS.len()]
+}
+
+/// Keeps `date` far enough from the ends of the calendar to show its whole
+/// week.
+fn clamp_date(date: NaiveDate) -> NaiveDate {
+    date.clamp(NaiveDate::MIN + Days::new(14), NaiveDate::MAX - Days::new(14))
+}
+
+#[derive(PartialEq)]
+enum Focus {
+    Timeline,
+    Totals,
+}
+
+struct App {
+    conn: Connection,
+    host: Option<String>,
+    idle: bool,
+    date: NaiveDate,
+    week: bool,
+    days: Vec<Day<Local>>,
+    spans: Vec<Span>,
+    /// Time per class of the shown days, longest first.
+    totals: Vec<(String, i64)>,
+    focus: Focus,
+    /// Selected day and row of the timeline.
+    cursor: (usize, usize),
+    /// Number of rows of the timeline in the last frame.
+    rows: usize,
+    /// Seconds covered by each row of the timeline.
+    row_seconds: i64,
+    selected_class: ListState,
+}
+
+impl App {
+    /// Loads the spans of the day or week containing `date`.
+    fn load(&mut self) {
+        let first = if self.week {
+            self.date.week(Weekday::Mon).first_day()
+        } else {
+            self.date
+        };
+        let last = if self.week { first.checked_add_days(Days::new(6)).unwrap() } else { first };
+        self.days = day::days(&Local, first, last);
+        let filter = db::Filter {
+            start: self.days.first().unwrap().start.timestamp(),
+            end: self.days.last().unwrap().end.timestamp(),
+            idle: self.idle,
+            host: self.host.clone(),
+        };
+        let samples = db::samples(&self.conn, &filter).unwrap();
+        self.spans = spans::merge(&samples);
+
+        let mut totals: HashMap<String, i64> = HashMap::new();
+        for s in &self.spans {
+            *totals.entry(s.class.clone()).or_default() += s.seconds();
+        }
+        self.totals = totals.into_iter().collect();
+        self.totals.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        self.cursor.0 = self.cursor.0.min(self.days.len() - 1);
+        self.selected_class.select(if self.totals.is_empty() { None } else { Some(0) });
+    }
+
+    fn longest_day(&self) -> i64 {
+        self.days.iter().map(|d| d.seconds()).max().unwrap()
+    }
+
+    /// The time range of a row of the timeline, rows cover the same time on
+    /// every day so days with a DST transition end earlier or later.
+    fn row_range(&self, day: &Day<Local>, row: usize) -> (i64, i64) {
+        let start = day.start.timestamp() + row as i64 * self.row_seconds;
+        (start, (start + self.row_seconds).min(day.end.timestamp()))
+    }
+
+    /// Spans within `start <= t < end`.
+    fn spans_in(&self, start: i64, end: i64) -> Vec<Span> {
+        spans::clip(&self.spans, start, end)
+    }
+
+    fn move_cursor(&mut self, days: isize, rows: isize) {
+        let day = (self.cursor.0 as isize + days).clamp(0, self.days.len() as isize - 1);
+        let row = (self.cursor.1 as isize + rows).clamp(0, self.rows.max(1) as isize - 1);
+        self.cursor = (day as usize, row as usize);
+    }
+
+    fn move_date(&mut self, forward: bool) {
+        let step = Days::new(if self.week { 7 } else { 1 });
+        let date = if forward {
+            self.date.checked_add_days(step)
+        } else {
+            self.date.checked_sub_days(step)
+        };
+        self.date = clamp_date(date.unwrap_or(self.date));
+        self.load();
+    }
+
+    /// Label of a row of the timeline, the wall clock if all shown days are
+    /// equally long, otherwise the clocks were changed within the week and
+    /// the rows are labeled with the time since midnight.
+    fn row_label(&self, row: usize) -> String {
+        let offset = row as i64 * self.row_seconds;
+        if self.days.iter().all(|d| d.seconds() == self.days[0].seconds()) {
+            self.days[0].clock(offset)
+        } else {
+            format!("+{}:{:02}", offset / 3600, offset % 3600 / 60)
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, help] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [timeline, side] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+        let [totals, details] = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(side);
+
+        self.draw_timeline(frame, timeline);
+        self.draw_totals(frame, totals);
+        self.draw_details(frame, details);
+        frame.render_widget(
+            Paragraph::new("←/→ day or week  w week  ↑/↓ select  h/l day in week  tab switch pane  t today  i idle  q quit")
+                .style(Style::default().fg(Color::DarkGray)),
+            help,
+        );
+    }
+
+    fn draw_timeline(&mut self, frame: &mut Frame, area: Rect) {
+        let title = if self.week {
+            format!(" Week {} ", self.days[0].date.format("%G-W%V"))
+        } else {
+            format!(" {} ", self.date.format("%a, %Y-%m-%d"))
+        };
+        let block = Block::bordered().title(title).border_style(self.border(Focus::Timeline));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        // one row for the dates, the rest for the day in steps of round minutes
+        let available = (inner.height as i64 - 1).max(1);
+        let longest = self.longest_day();
+        self.row_seconds = [300, 600, 900, 1200, 1800, 3600, 7200, 10800].into_iter()
+            .find(|s| (longest + s - 1) / s <= available)
+            .unwrap_or(longest / available + 1);
+        self.rows = ((longest + self.row_seconds - 1) / self.row_seconds) as usize;
+        self.cursor.1 = self.cursor.1.min(self.rows - 1);
+        let axis = 7;
+        let width = ((inner.width as usize).saturating_sub(axis) / self.days.len()).max(1);
+
+        let mut lines = vec![];
+        let mut header = vec![Text::raw(" ".repeat(axis))];
+        for d in &self.days {
+            header.push(Text::styled(format!("{:<width$.width$}", d.date.format("%a %d").to_string()), Style::default().add_modifier(Modifier::BOLD)));
+        }
+        lines.push(Line::from(header));
+
+        for row in 0..self.rows {
+            let mut line = vec![Text::styled(
+                format!("{:>width$} ", self.row_label(row), width = axis - 1),
+                Style::default().fg(Color::DarkGray),
+            )];
+            for (i, d) in self.days.iter().enumerate() {
+                let (start, end) = self.row_range(d, row);
+                // show the class taking up most of the row
+                let mut classes: HashMap<&str, i64> = HashMap::new();
+                let spans = if start < end { self.spans_in(start, end) } else { vec![] };
+                for s in &spans {
+                    *classes.entry(s.class.as_str()).or_default() += s.seconds();
+                }
+                let class = classes.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0))).map(|(c, _)| c.to_string());
+                let mut style = match &class {
+                    Some(c) => Style::default().bg(color(c)).fg(Color::Black),
+                    None => Style::default(),
+                };
+                if self.focus == Focus::Timeline && self.cursor == (i, row) {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                let label = class.unwrap_or_default();
+                // keep a gap between the days of a week
+                let w = if self.days.len() > 1 { width.saturating_sub(1) } else { width };
+                line.push(Text::styled(format!("{:<w$.w$}", label), style));
+                line.push(Text::raw(" ".repeat(width - w)));
+            }
+            lines.push(Line::from(line));
+        }
+        frame.render_widget(Paragraph::new(lines), inner);
+    }
+
+    fn draw_totals(&mut self, frame: &mut Frame, area: Rect) {
+        let total: i64 = self.totals.iter().map(|t| t.1).sum();
+        let items: Vec<ListItem> = self.totals.iter().map(|(class, secs)| {
+            ListItem::new(Line::from(vec![
+                Text::styled("█ ", Style::default().fg(color(class))),
+                Text::raw(format!("{:>9}  {}", fmt_duration(*secs), class)),
+            ]))
+        }).collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(" Total {} ", fmt_duration(total))).border_style(self.border(Focus::Totals)))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        if self.focus == Focus::Totals {
+            frame.render_stateful_widget(list, area, &mut self.selected_class);
+        } else {
+            frame.render_widget(list, area);
+        }
+    }
+
+    /// Titles of the selected block of the timeline or of the selected class.
+    fn draw_details(&mut self, frame: &mut Frame, area: Rect) {
+        let (title, spans) = match self.focus {
+            Focus::Timeline => {
+                let (start, end) = self.row_range(&self.days[self.cursor.0], self.cursor.1);
+                let fmt = |ts: i64| Local.timestamp_opt(ts, 0).unwrap().format("%H:%M").to_string();
+                (format!(" {} {} - {} ", self.days[self.cursor.0].date.format("%a"), fmt(start), fmt(end)), self.spans_in(start, end))
+            },
+            Focus::Totals => {
+                let class = self.selected_class.selected().and_then(|i| self.totals.get(i)).map(|t| t.0.clone()).unwrap_or_default();
+                let spans = self.spans.iter().filter(|s| s.class == class).cloned().collect();
+                (format!(" {} ", class), spans)
+            },
+        };
+
+        let mut titles: HashMap<(String, String), i64> = HashMap::new();
+        for s in &spans {
+            *titles.entry((s.class.clone(), s.title.clone())).or_default() += s.seconds();
+        }
+        let mut titles: Vec<((String, String), i64)> = titles.into_iter().collect();
+        titles.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let lines: Vec<Line> = titles.iter().map(|((class, title), secs)| Line::from(vec![
+            Text::styled("█ ", Style::default().fg(color(class))),
+            Text::raw(format!("{:>9}  {}", fmt_duration(*secs), title)),
+        ])).collect();
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), area);
+    }
+
+    fn border(&self, focus: Focus) -> Style {
+        if self.focus == focus {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+    let date = match args.start {
+        Some(s) => day::parse_date(&s).map(clamp_date).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }),
+        None => Local::now().date_naive(),
+    };
+
+    let conn = db::open_read_only(&db).unwrap_or_else(|e| {
+        eprintln!("Could not open {}: {}", db, e);
+        process::exit(1);
+    });
+    let mut app = App {
+        conn,
+        host: args.host,
+        idle: args.idle.is_some() && args.idle.unwrap(),
+        date,
+        week: false,
+        days: vec![],
+        spans: vec![],
+        totals: vec![],
+        focus: Focus::Timeline,
+        cursor: (0, 0),
+        rows: 1,
+        row_seconds: 3600,
+        selected_class: ListState::default(),
+    };
+    app.load();
+
+    let mut terminal = ratatui::init();
+    loop {
+        terminal.draw(|f| app.draw(f)).unwrap();
+        let Event::Key(key) = event::read().unwrap() else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match (key.code, &app.focus) {
+            (KeyCode::Char('q') | KeyCode::Esc, _) => break,
+            (KeyCode::Left, _) => app.move_date(false),
+            (KeyCode::Right, _) => app.move_date(true),
+            (KeyCode::Char('w'), _) => {
+                app.week = !app.week;
+                app.load();
+            },
+            (KeyCode::Char('t'), _) => {
+                app.date = Local::now().date_naive();
+                app.load();
+            },
+            (KeyCode::Char('i'), _) => {
+                app.idle = !app.idle;
+                app.load();
+            },
+            (KeyCode::Tab, Focus::Timeline) => app.focus = Focus::Totals,
+            (KeyCode::Tab, Focus::Totals) => app.focus = Focus::Timeline,
+            (KeyCode::Up | KeyCode::Char('k'), Focus::Timeline) => app.move_cursor(0, -1),
+            (KeyCode::Down | KeyCode::Char('j'), Focus::Timeline) => app.move_cursor(0, 1),
+            (KeyCode::Char('h'), Focus::Timeline) => app.move_cursor(-1, 0),
+            (KeyCode::Char('l'), Focus::Timeline) => app.move_cursor(1, 0),
+            (KeyCode::Up | KeyCode::Char('k'), Focus::Totals) => app.selected_class.select_previous(),
+            (KeyCode::Down | KeyCode::Char('j'), Focus::Totals) => app.selected_class.select_next(),
+            _ => {},
+        }
+    }
+    ratatui::restore();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_stay_within_the_calendar() {
+        for date in [NaiveDate::MIN, NaiveDate::MAX] {
+            let first = clamp_date(date).week(Weekday::Mon).first_day();
+            assert_eq!(day::days(&Local, first, first + Days::new(6)).len(), 7);
+        }
+        let date = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        assert_eq!(clamp_date(date), date);
+    }
+}
@@ -0,0 +1,306 @@
+use chrono::prelude::*;
+use clap::Parser;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::time::Duration;
+use tracking::config::{self, Config};
+use tracking::day::{self, Day};
+use tracking::db;
+use tracking::spans::{self, Span};
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to connect to, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(short, long, help = "The config file, defaults to ~/.config/tracking/config.json can also be set with TRACKING_CONFIG environment variable")]
+    config: Option<String>,
+
+    #[arg(short, long, help = "The address to listen on, only local connections are accepted by default", default_value = "127.0.0.1:8123")]
+    listen: String,
+}
+
+
+const DASHBOARD: &str = include_str!("../../res/dashboard.html");
+/// Requests are served one after another, a client that stops sending or
+/// reading is dropped after this long so it can't block the others.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(body: json::JsonValue) -> Response {
+        Response { status: "200 OK", content_type: "application/json", body: body.dump() }
+    }
+
+    fn error(status: &'static str, message: String) -> Response {
+        Response { status, content_type: "application/json", body: json::object!{ error: message }.dump() }
+    }
+}
+
+/// The parameters of an API request.
+struct Query {
+    days: Vec<Day<Local>>,
+    filter: db::Filter,
+    params: HashMap<String, String>,
+}
+
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    },
+                    None => out.push(b'%'),
+                }
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn parse_params(query: &str) -> HashMap<String, String> {
+    query.split('&').filter(|p| !p.is_empty()).map(|p| {
+        let (k, v) = p.split_once('=').unwrap_or((p, ""));
+        (decode(k), decode(v))
+    }).collect()
+}
+
+/// Reads the date range, host and idle parameters shared by all endpoints,
+/// the range defaults to today.
+fn parse_query(params: HashMap<String, String>) -> Result<Query, String> {
+    let today = Local::now().date_naive();
+    let date = |key: &str| match params.get(key).filter(|d| !d.is_empty()) {
+        Some(d) => day::parse_date(d),
+        None => Ok(today),
+    };
+    let first = date("start")?;
+    let last = date("end")?;
+    if last < first {
+        return Err("The end date must not be before the start date".to_string());
+    }
+    if (last - first).num_days() > 366 {
+        return Err("At most a year can be shown at once".to_string());
+    }
+
+    let days = day::days(&Local, first, last);
+    let filter = db::Filter {
+        start: days.first().unwrap().start.timestamp(),
+        end: days.last().unwrap().end.timestamp(),
+        idle: params.get("idle").is_some_and(|i| i == "1" || i == "true"),
+        host: params.get("host").filter(|h| !h.is_empty()).cloned(),
+    };
+    Ok(Query { days, filter, params })
+}
+
+/// Name to group `class` under, the class itself or its category from the
+/// config depending on the `by` parameter.
+fn group(query: &Query, config: &Config, class: &str) -> String {
+    match query.params.get("by").map(|b| b.as_str()) {
+        Some("category") => config.category(class).to_string(),
+        _ => class.to_string(),
+    }
+}
+
+fn span_json(s: &Span, name: String) -> json::JsonValue {
+    json::object!{
+        name: name,
+        class: s.class.clone(),
+        title: s.title.clone(),
+        idle: s.idle,
+        host: s.host.clone(),
+        start: s.start,
+        end: s.end,
+    }
+}
+
+/// Sums up the seconds per key in descending order.
+fn totals<'a, I: Iterator<Item = (String, &'a Span)>>(spans: I) -> json::JsonValue {
+    let mut sums: HashMap<String, i64> = HashMap::new();
+    for (key, span) in spans {
+        *sums.entry(key).or_default() += span.seconds();
+    }
+    let mut sums: Vec<(String, i64)> = sums.into_iter().collect();
+    sums.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    json::JsonValue::Array(sums.into_iter().map(|(name, seconds)| json::object!{ name: name, seconds: seconds }).collect())
+}
+
+/// The spans of every day for the timeline, laid out by the time elapsed
+/// since the start of the day so days with DST transitions fit.
+fn api_days(conn: &Connection, config: &Config, query: &Query) -> rusqlite::Result<json::JsonValue> {
+    let samples = db::samples(conn, &query.filter)?;
+    let merged = spans::merge(&samples);
+    let days = query.days.iter().map(|d| {
+        let spans: Vec<json::JsonValue> = spans::clip(&merged, d.start.timestamp(), d.end.timestamp())
+            .iter().map(|s| span_json(s, group(query, config, &s.class))).collect();
+        json::object!{
+            date: d.date.format("%Y-%m-%d").to_string(),
+            start: d.start.timestamp(),
+            seconds: d.seconds(),
+            transitions: d.transitions(),
+            spans: spans,
+        }
+    }).collect();
+    Ok(json::JsonValue::Array(days))
+}
+
+/// Time per class or category over the whole range.
+fn api_summary(conn: &Connection, config: &Config, query: &Query) -> rusqlite::Result<json::JsonValue> {
+    let samples = db::samples(conn, &query.filter)?;
+    let merged = spans::merge(&samples);
+    Ok(totals(merged.iter().map(|s| (group(query, config, &s.class), s))))
+}
+
+/// Time per title of the classes in the `name` class or category.
+fn api_titles(conn: &Connection, config: &Config, query: &Query) -> rusqlite::Result<json::JsonValue> {
+    let name = query.params.get("name").cloned().unwrap_or_default();
+    let samples = db::samples(conn, &query.filter)?;
+    let merged = spans::merge(&samples);
+    Ok(totals(merged.iter().filter(|s| group(query, config, &s.class) == name).map(|s| (s.title.clone(), s))))
+}
+
+fn handle(path: &str, db: &str, config: &Config) -> Response {
+    let (path, params) = path.split_once('?').unwrap_or((path, ""));
+    if path == "/" {
+        return Response { status: "200 OK", content_type: "text/html; charset=utf-8", body: DASHBOARD.to_string() };
+    }
+
+    let conn = match db::open_read_only(db) {
+        Ok(c) => c,
+        Err(e) => return Response::error("500 Internal Server Error", format!("Could not open {}: {}", db, e)),
+    };
+    if path == "/api/hosts" {
+        return match db::hosts(&conn) {
+            Ok(hosts) => Response::json(hosts.into()),
+            Err(e) => Response::error("500 Internal Server Error", e.to_string()),
+        };
+    }
+    let query = match parse_query(parse_params(params)) {
+        Ok(q) => q,
+        Err(e) => return Response::error("400 Bad Request", e),
+    };
+    let result = match path {
+        "/api/days" => api_days(&conn, config, &query),
+        "/api/summary" => api_summary(&conn, config, &query),
+        "/api/titles" => api_titles(&conn, config, &query),
+        _ => return Response::error("404 Not Found", format!("Unknown path {}", path)),
+    };
+    match result {
+        Ok(body) => Response::json(body),
+        Err(e) => Response::error("500 Internal Server Error", e.to_string()),
+    }
+}
+
+/// Reads the request line and the `Host` header, the other headers are not
+/// needed.
+fn read_request<R: BufRead>(mut reader: R) -> std::io::Result<(String, Option<String>)> {
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut host = None;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("host") {
+                host = Some(value.trim().to_lowercase());
+            }
+        }
+        line.clear();
+    }
+    Ok((request, host))
+}
+
+/// The `Host` headers the dashboard answers to. Listening on localhost alone
+/// doesn't keep websites from reading the titles, they can point a domain of
+/// their own at it (DNS rebinding), but their requests carry that domain.
+fn allowed_hosts(listen: &str, port: u16) -> Vec<String> {
+    vec![format!("127.0.0.1:{}", port), format!("localhost:{}", port), format!("[::1]:{}", port), listen.to_lowercase()]
+}
+
+fn serve(stream: TcpStream, db: &str, config: &Config, hosts: &[String]) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let (request, host) = read_request(BufReader::new(&stream))?;
+
+    let parts: Vec<&str> = request.split_whitespace().collect();
+    let response = match parts.as_slice() {
+        _ if !host.is_some_and(|h| hosts.contains(&h)) => Response::error("403 Forbidden", "Unknown host".to_string()),
+        ["GET", path, ..] => handle(path, db, config),
+        _ => Response::error("405 Method Not Allowed", "Only GET requests are supported".to_string()),
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status, response.content_type, response.body.len(), response.body,
+    )?;
+    stream.flush()
+}
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+    let config = Config::load(&config::path(args.config)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    let listener = TcpListener::bind(&args.listen).unwrap_or_else(|e| {
+        eprintln!("Could not listen on {}: {}", args.listen, e);
+        process::exit(1);
+    });
+    let port = listener.local_addr().map(|a| a.port()).unwrap_or_default();
+    let hosts = allowed_hosts(&args.listen, port);
+    println!("Serving the dashboard of {} on http://{}", db, args.listen);
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        if let Err(e) = serve(stream, &db, &config, &hosts) {
+            eprintln!("Request failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_host_header() {
+        let request = "GET /api/hosts HTTP/1.1\r\nAccept: */*\r\nHOST: LocalHost:8123\r\n\r\n";
+        let (line, host) = read_request(request.as_bytes()).unwrap();
+        assert_eq!(line.trim_end(), "GET /api/hosts HTTP/1.1");
+        assert_eq!(host.as_deref(), Some("localhost:8123"));
+
+        let (_, host) = read_request("GET / HTTP/1.0\r\n\r\n".as_bytes()).unwrap();
+        assert_eq!(host, None);
+    }
+
+    #[test]
+    fn only_local_hosts_are_allowed() {
+        let hosts = allowed_hosts("0.0.0.0:8123", 8123);
+        assert!(hosts.contains(&"127.0.0.1:8123".to_string()));
+        assert!(hosts.contains(&"localhost:8123".to_string()));
+        assert!(hosts.contains(&"0.0.0.0:8123".to_string()));
+        assert!(!hosts.contains(&"localhost:80".to_string()));
+        assert!(!hosts.contains(&"evil.example:8123".to_string()));
+    }
+}
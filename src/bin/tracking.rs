@@ -1,15 +1,19 @@
-use rusqlite::Connection;
 use rusqlite::ErrorCode::*;
 use std::collections::VecDeque;
 use std::env;
 use std::path::Path;
 use std::time::Duration;
-use std::process::Command;
-use json;
+use std::process::{self, Command};
 use chrono::Local;
 use lazy_static::lazy_static;
 use clap::Parser;
+use tokio::signal::{self, unix::{signal, SignalKind}};
 use tokio::time;
+use tracking::config::{self, Config};
+use tracking::db;
+use tracking::focus;
+use tracking::runs;
+use tracking::table::fmt_duration;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -18,7 +22,10 @@ struct Args {
     database: Option<String>,
 
     #[arg(short, long)]
-    idlefile: Option<String>
+    idlefile: Option<String>,
+
+    #[arg(short, long)]
+    config: Option<String>
 }
 
 
@@ -46,7 +53,13 @@ fn get_focused_window(obj: &json::JsonValue) -> &json::JsonValue {
         }
     }
 
-    return &EMPTY;
+    &EMPTY
+}
+
+fn notify(message: &str) {
+    if let Err(e) = Command::new("notify-send").args(["tracking", message]).spawn() {
+        eprintln!("Could not call notify-send: {}", e);
+    }
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -58,8 +71,8 @@ async fn main() {
     };
 
     let args = Args::parse();
-    if args.database.is_some() {
-        db = args.database.unwrap();
+    if let Some(d) = args.database {
+        db = d;
     }
 
     let mut idle_file = if let Ok(s) = env::var("TRACKING_IDLE_FILE") {
@@ -68,30 +81,43 @@ async fn main() {
         "/tmp/tracking-idle".to_string()
     };
 
-    if args.idlefile.is_some() {
-        idle_file = args.idlefile.unwrap();
+    if let Some(f) = args.idlefile {
+        idle_file = f;
     }
 
 
-    let conn = Connection::open(&db).unwrap();
-    conn.execute(
-        "create table if not exists tracking (
-            id integer primary key,
-            class text not null,
-            title text not null,
-            idle integer not null,
-            ts integer not null unique
-    );",
-    (),
-    ).unwrap();
+    let config = Config::load(&config::path(args.config)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    let conn = db::open(&db).unwrap_or_else(|e| {
+        eprintln!("Could not open {}: {}", db, e);
+        process::exit(1);
+    });
+    let host = db::hostname();
 
     let mut interval = time::interval(Duration::from_secs(10));
-    let mut cache: VecDeque<[String;4]> = VecDeque::new();
+    let mut cache: VecDeque<[String;5]> = VecDeque::new();
+    // whether the last sample of the running focus session was distracting
+    let mut distracted = false;
+
+    // heartbeats tell times the daemon wasn't running apart from idle ones
+    let mut run = runs::start(&conn, &host, Local::now().timestamp()).unwrap();
+    let mut terminate = signal(SignalKind::terminate()).unwrap();
 
     eprintln!("Started logging to {} at {}", db, Local::now());
 
     'main: loop {
-        interval.tick().await;
+        tokio::select! {
+            _ = interval.tick() => {},
+            _ = terminate.recv() => break,
+            _ = signal::ctrl_c() => break,
+        }
+
+        if let Err(e) = runs::beat(&conn, &mut run, Local::now().timestamp()) {
+            eprintln!("Could not record heartbeat: {}", e);
+        }
 
         let output = Command::new("i3-msg").args(["-t", "get_tree"]).output().expect("Could not call i3-msg -t get_tree");
         let output = String::from_utf8(output.stdout).unwrap_or_default();
@@ -119,14 +145,42 @@ async fn main() {
             "0".to_string()
         };
 
-        let entry = [focus_entry.class.clone(), focus_entry.title.clone(), idle, focus_entry.ts.clone()];
+        // focus sessions are started by trackingctl, the daemon finishes them
+        match focus::finish_overdue(&conn, &host, Local::now().timestamp(), &config) {
+            Ok(finished) => {
+                for s in finished.iter().map(|s| &s.summary) {
+                    notify(&format!("Focus session over: {} focused, {} distracted by {} distractions",
+                        fmt_duration(s.focused), fmt_duration(s.distracted), s.distractions));
+                }
+            },
+            Err(e) => eprintln!("Could not finish focus session: {}", e),
+        }
+        match focus::running(&conn, &host) {
+            Ok(Some(_)) => {
+                let now_distracted = idle == "0" && config.distracting(&focus_entry.class);
+                if now_distracted && !distracted {
+                    notify(&format!("{} is distracting you from your focus session", focus_entry.class));
+                }
+                distracted = now_distracted;
+            },
+            Ok(None) => distracted = false,
+            Err(e) => eprintln!("Could not check for focus sessions: {}", e),
+        }
 
-        while cache.len() > 0 {
+        // windows excluded by the privacy rules are not recorded at all,
+        // cached entries are written with the next recorded one
+        let Some(title) = config.privacy.apply(&focus_entry.class, &focus_entry.title) else {
+            continue;
+        };
+
+        let entry = [focus_entry.class.clone(), title, idle, focus_entry.ts.clone(), host.clone()];
+
+        while !cache.is_empty() {
             eprintln!("Cache not empty, attempting to write to db");
             if let Some(centry) = cache.front() {
                 eprintln!("Attempting to insert entry: {:?}", centry);
                 if let Err(e) = conn.execute(
-                        "INSERT INTO tracking (class, title, idle, ts) values (?1, ?2, ?3, ?4);",
+                        "INSERT INTO tracking (class, title, idle, ts, host) values (?1, ?2, ?3, ?4, ?5);",
                                     centry.clone(),
                     ) {
                     eprintln!("Error logging cached entry: {}", e);
@@ -146,7 +200,7 @@ async fn main() {
         }
 
         if let Err(e) = conn.execute(
-                "INSERT INTO tracking (class, title, idle, ts) values (?1, ?2, ?3, ?4);",
+                "INSERT INTO tracking (class, title, idle, ts, host) values (?1, ?2, ?3, ?4, ?5);",
                         entry.clone(),
             ) {
                 eprintln!("Error logging entry: {}", e);
@@ -155,4 +209,8 @@ async fn main() {
             }
     }
 
+    if let Err(e) = runs::stop(&conn, &mut run, Local::now().timestamp()) {
+        eprintln!("Could not record stop: {}", e);
+    }
+    eprintln!("Stopped logging at {}", Local::now());
 }
@@ -0,0 +1,282 @@
+use chrono::prelude::*;
+use clap::{Args as ClapArgs, ArgAction, Parser, Subcommand};
+use rusqlite::Connection;
+use std::process;
+use tracking::annotations::{self, Annotation};
+use tracking::config::{self, Config};
+use tracking::day::{self, parse_date, Day};
+use tracking::db;
+use tracking::edits::{self, Edit, Kind};
+use tracking::focus::{self, Session};
+use tracking::table::{fmt_duration, Table};
+
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short, long, help = "The database to connect to, defaults to 'tracking.db' can also be set with TRACKING_DB environment variable")]
+    database: Option<String>,
+
+    #[arg(short, long, help = "The config file, defaults to ~/.config/tracking/config.json can also be set with TRACKING_CONFIG environment variable")]
+    config: Option<String>,
+
+    #[arg(long, help = "The date of times given as HH:MM in the format YYYY-MM-DD, defaults to today", global = true)]
+    date: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Add time spent away from the computer, replacing what was tracked
+    Add {
+        #[command(flatten)]
+        range: Range,
+
+        #[arg(long, help = "The class to record the time as, e.g. meeting")]
+        class: String,
+
+        #[arg(long, help = "The title to record the time with", default_value = "")]
+        title: String,
+
+        #[arg(long, help = "The host to record the time for, defaults to this machine")]
+        host: Option<String>,
+    },
+    /// Change the class or title of the tracked time
+    Relabel {
+        #[command(flatten)]
+        range: Range,
+
+        #[arg(long, help = "The new class, kept if not given")]
+        class: Option<String>,
+
+        #[arg(long, help = "The new title, kept if not given")]
+        title: Option<String>,
+
+        #[arg(long, help = "Only change the time tracked on this host, all hosts by default")]
+        host: Option<String>,
+    },
+    /// Mark the tracked time as idle
+    Idle {
+        #[command(flatten)]
+        range: Range,
+
+        #[arg(long, help = "Only change the time tracked on this host, all hosts by default")]
+        host: Option<String>,
+    },
+    /// Mark the tracked time as not idle
+    Active {
+        #[command(flatten)]
+        range: Range,
+
+        #[arg(long, help = "Only change the time tracked on this host, all hosts by default")]
+        host: Option<String>,
+    },
+    /// Delete the tracked time
+    Delete {
+        #[command(flatten)]
+        range: Range,
+
+        #[arg(long, help = "Only delete the time tracked on this host, all hosts by default")]
+        host: Option<String>,
+    },
+    /// Tag a time range, e.g. with the meeting or incident it was spent on
+    Tag {
+        #[command(flatten)]
+        range: Range,
+
+        #[arg(help = "The tag, e.g. sprint-planning")]
+        tag: String,
+
+        #[arg(long, help = "A note to store with the tag", default_value = "")]
+        note: String,
+    },
+    /// Remove a tag again
+    Untag {
+        #[arg(help = "The id of the tag as shown by list")]
+        id: i64,
+    },
+    /// Start a focus session, show the running one or stop it early
+    Focus {
+        #[arg(help = "Length of the session, e.g. 25m or 1h30m, shows the running session if not given")]
+        duration: Option<String>,
+
+        #[arg(long, help = "What the session is about", default_value = "")]
+        label: String,
+
+        #[arg(long, help = "Stop the running session", action = ArgAction::SetTrue)]
+        stop: Option<bool>,
+    },
+    /// List the edits and tags of a day
+    List,
+    /// Remove an edit again
+    Undo {
+        #[arg(help = "The id of the edit as shown by list")]
+        id: i64,
+    },
+}
+
+#[derive(ClapArgs, Debug)]
+struct Range {
+    #[arg(help = "Start of the range as HH:MM or YYYY-MM-DD HH:MM")]
+    from: String,
+
+    #[arg(help = "End of the range as HH:MM or YYYY-MM-DD HH:MM, not included")]
+    to: String,
+}
+
+
+/// Parses `HH:MM` on `date` or a full `YYYY-MM-DD HH:MM`.
+fn parse_time(s: &str, date: NaiveDate) -> Result<i64, String> {
+    let naive = if let Ok(time) = NaiveTime::parse_from_str(s, "%H:%M") {
+        date.and_time(time)
+    } else {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+            .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M"))
+            .map_err(|_| format!("Invalid time {}, must be of format HH:MM or YYYY-MM-DD HH:MM", s))?
+    };
+    Ok(day::resolve_local(&Local, naive).timestamp())
+}
+
+fn fmt_time(ts: i64) -> String {
+    Local.timestamp_opt(ts, 0).unwrap().format("%Y-%m-%d %H:%M").to_string()
+}
+
+fn focus_command(conn: &Connection, duration: Option<String>, label: String, stop: bool, config: Option<String>) {
+    let host = db::hostname();
+    let now = Local::now().timestamp();
+    let config = Config::load(&config::path(config)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    // sessions that were over while the daemon wasn't running are still open
+    for session in focus::finish_overdue(conn, &host, now, &config).unwrap() {
+        println!("Finished focus session {} that was over at {}", session.id, fmt_time(session.end));
+    }
+    let running = focus::running(conn, &host).unwrap();
+    match (running, duration) {
+        (Some(mut session), _) if stop => {
+            let ended = now.min(session.end);
+            focus::finish(conn, &mut session, ended, &config).unwrap();
+            println!("Stopped focus session {}: {} focused, {} distracted", session.id, fmt_duration(session.summary.focused), fmt_duration(session.summary.distracted));
+        },
+        (None, _) if stop => {
+            eprintln!("No focus session is running");
+            process::exit(1);
+        },
+        (Some(session), Some(_)) => {
+            eprintln!("Focus session {} is already running until {}", session.id, fmt_time(session.end));
+            process::exit(1);
+        },
+        (Some(session), None) => {
+            println!("Focus session {} {}runs until {}, {} left", session.id,
+                if session.label.is_empty() { "".to_string() } else { format!("on {} ", session.label) },
+                fmt_time(session.end), fmt_duration((session.end - now).max(0)));
+        },
+        (None, None) => println!("No focus session is running"),
+        (None, Some(duration)) => {
+            let duration = focus::parse_duration(&duration).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            });
+            let session = Session { start: now, end: now + duration, host, label, ..Session::default() };
+            let id = focus::start(conn, &session).unwrap();
+            println!("Started focus session {} until {}, the tracking daemon notifies when it is over", id, fmt_time(session.end));
+        },
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let db = db::path(args.database);
+    let date = match args.date {
+        Some(d) => parse_date(&d).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }),
+        None => Local::now().date_naive(),
+    };
+    let conn = db::open(&db).unwrap();
+
+    let range = |range: &Range| {
+        let parse = |s: &str| parse_time(s, date).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        let (start, end) = (parse(&range.from), parse(&range.to));
+        if end <= start {
+            eprintln!("The end of the range must be after its start");
+            process::exit(1);
+        }
+        (start, end)
+    };
+    let edit = |kind: Kind, r: Range, class: Option<String>, title: Option<String>, host: Option<String>| {
+        let (start, end) = range(&r);
+        Edit {
+            id: 0,
+            kind,
+            start,
+            end,
+            class: class.unwrap_or_default(),
+            title: title.unwrap_or_default(),
+            host: host.unwrap_or_default(),
+        }
+    };
+    let edit = match args.command {
+        Command::Add { range, class, title, host } => edit(Kind::Add, range, Some(class), Some(title), Some(host.unwrap_or_else(db::hostname))),
+        Command::Relabel { class: None, title: None, .. } => {
+            eprintln!("Relabeling needs a new class or title");
+            process::exit(1);
+        },
+        Command::Relabel { range, class, title, host } => edit(Kind::Relabel, range, class, title, host),
+        Command::Idle { range, host } => edit(Kind::Idle, range, None, None, host),
+        Command::Active { range, host } => edit(Kind::Active, range, None, None, host),
+        Command::Delete { range, host } => edit(Kind::Delete, range, None, None, host),
+        Command::Tag { range: r, tag, note } => {
+            let (start, end) = range(&r);
+            let annotation = Annotation { id: 0, start, end, tag, note };
+            let id = annotations::insert(&conn, &annotation).unwrap();
+            println!("Added tag {}: {} {} to {}", id, annotation.tag, fmt_time(start), fmt_time(end));
+            return;
+        },
+        Command::Untag { id } => {
+            if !annotations::remove(&conn, id).unwrap() {
+                eprintln!("There is no tag {}", id);
+                process::exit(1);
+            }
+            return;
+        },
+        Command::Focus { duration, label, stop } => {
+            focus_command(&conn, duration, label, stop.is_some() && stop.unwrap(), args.config);
+            return;
+        },
+        Command::List => {
+            let day = Day::new(&Local, date);
+            let mut table = Table::new(&["Id", "Edit", "From", "To", "Duration", "Class", "Title", "Host"]);
+            for e in edits::load(&conn, day.start.timestamp(), day.end.timestamp(), &None).unwrap() {
+                table.push(vec![
+                    e.id.to_string(), e.kind.name().to_string(), fmt_time(e.start), fmt_time(e.end),
+                    fmt_duration(e.end - e.start), e.class, e.title, e.host,
+                ]);
+            }
+            print!("{}", table.render_text());
+
+            let mut table = Table::new(&["Id", "Tag", "From", "To", "Duration", "Note"]);
+            for a in annotations::load(&conn, day.start.timestamp(), day.end.timestamp()).unwrap() {
+                table.push(vec![a.id.to_string(), a.tag, fmt_time(a.start), fmt_time(a.end), fmt_duration(a.end - a.start), a.note]);
+            }
+            print!("\n{}", table.render_text());
+            return;
+        },
+        Command::Undo { id } => {
+            if !edits::remove(&conn, id).unwrap() {
+                eprintln!("There is no edit {}", id);
+                process::exit(1);
+            }
+            return;
+        },
+    };
+    let id = edits::insert(&conn, &edit).unwrap();
+    println!("Added edit {}: {} {} to {}", id, edit.kind.name(), fmt_time(edit.start), fmt_time(edit.end));
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use crate::privacy::{Privacy, Rule};
+use crate::theme::Theme;
+use crate::timesheet::Timesheet;
+
+/// Settings shared by all programs, read from a JSON file such as:
+///
+/// ```json
+/// {
+///     "categories": {
+///         "dev": ["Alacritty", "code"],
+///         "web": ["firefox"]
+///     },
+///     "retention": {
+///         "keep_days": 90,
+///         "resolution": "span"
+///     },
+///     "privacy": {
+///         "salt": "something random",
+///         "rules": [
+///             {"class": "^firefox$", "title": "Private Browsing", "action": "exclude"},
+///             {"class": "KeePassXC", "action": "hash"},
+///             {"title": "Signal", "action": "truncate", "length": 6}
+///         ]
+///     },
+///     "focus": {
+///         "distracting": ["web", "discord"],
+///         "chat": ["slack", "discord"]
+///     },
+///     "chart": {
+///         "theme": "dark",
+///         "grid": "#505050",
+///         "font": "DejaVu Sans Mono",
+///         "font_size": 14
+///     },
+///     "timesheet": {
+///         "rounding": {"increment": 15, "mode": "up", "per": "day"},
+///         "clients": {
+///             "acme": {
+///                 "rules": [{"class": "code", "title": "acme", "project": "website"}]
+///             }
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Maps lowercased window classes to the category they belong to.
+    pub categories: HashMap<String, String>,
+    pub retention: Retention,
+    /// Rules applied to window titles before they are stored.
+    pub privacy: Privacy,
+    /// Clients that time is billed to by `timesheet`.
+    pub timesheet: Timesheet,
+    pub focus: Focus,
+    /// Colors and font of `gen_chart`.
+    pub chart: Theme,
+}
+
+/// How long samples are kept at full resolution before `compact` merges them.
+#[derive(Debug)]
+pub struct Retention {
+    pub keep_days: u64,
+    /// One of span, hour or day.
+    pub resolution: String,
+}
+
+impl Default for Retention {
+    fn default() -> Retention {
+        Retention {
+            keep_days: 90,
+            resolution: "span".to_string(),
+        }
+    }
+}
+
+/// Settings of focus sessions started with `trackingctl focus`.
+#[derive(Debug, Default)]
+pub struct Focus {
+    /// Lowercased classes and categories that count as distractions.
+    pub distracting: Vec<String>,
+    /// Lowercased classes and categories used to chat, `report --switches`
+    /// measures how long it takes to get back to work after switching to them.
+    pub chat: Vec<String>,
+}
+
+/// Category of classes that are not listed in any category.
+pub const UNCATEGORIZED: &str = "other";
+
+/// Resolves the config path from the `--config` flag, the `TRACKING_CONFIG`
+/// environment variable or `$XDG_CONFIG_HOME/tracking/config.json`, falling
+/// back to `~/.config/tracking/config.json`.
+pub fn path(arg: Option<String>) -> String {
+    if let Some(c) = arg {
+        return c;
+    }
+    if let Ok(s) = env::var("TRACKING_CONFIG") {
+        return s;
+    }
+    let dir = if let Ok(s) = env::var("XDG_CONFIG_HOME") {
+        s
+    } else {
+        format!("{}/.config", env::var("HOME").unwrap_or_default())
+    };
+    format!("{}/tracking/config.json", dir)
+}
+
+impl Config {
+    /// Loads the config at `path`, a missing file results in the default config.
+    pub fn load(path: &str) -> Result<Config, String> {
+        if !Path::new(path).exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read_to_string(path).map_err(|e| format!("Could not read config {}: {}", path, e))?;
+        let obj = json::parse(&content).map_err(|e| format!("Could not parse config {}: {}", path, e))?;
+        Config::from_json(&obj).map_err(|e| format!("Invalid config {}: {}", path, e))
+    }
+
+    pub fn from_json(obj: &json::JsonValue) -> Result<Config, String> {
+        let mut config = Config::default();
+        for (category, classes) in obj["categories"].entries() {
+            if !classes.is_array() {
+                return Err(format!("categories.{} must be a list of classes", category));
+            }
+            for class in classes.members() {
+                config.categories.insert(class.to_string().to_lowercase(), category.to_string());
+            }
+        }
+        let retention = &obj["retention"];
+        if !retention["keep_days"].is_null() {
+            config.retention.keep_days = retention["keep_days"].as_u64().ok_or("retention.keep_days must be a positive number")?;
+        }
+        if !retention["resolution"].is_null() {
+            config.retention.resolution = retention["resolution"].as_str().ok_or("retention.resolution must be a string")?.to_string();
+        }
+        let privacy = &obj["privacy"];
+        config.privacy.salt = privacy["salt"].as_str().unwrap_or_default().to_string();
+        for (i, rule) in privacy["rules"].members().enumerate() {
+            config.privacy.rules.push(Rule::from_json(rule).map_err(|e| format!("privacy.rules[{}]: {}", i, e))?);
+        }
+        for class in obj["focus"]["distracting"].members() {
+            config.focus.distracting.push(class.as_str().ok_or("focus.distracting must be a list of classes or categories")?.to_lowercase());
+        }
+        for class in obj["focus"]["chat"].members() {
+            config.focus.chat.push(class.as_str().ok_or("focus.chat must be a list of classes or categories")?.to_lowercase());
+        }
+        config.chart = Theme::from_json(&obj["chart"]).map_err(|e| format!("chart.{}", e))?;
+        config.timesheet = Timesheet::from_json(&obj["timesheet"]).map_err(|e| format!("timesheet.{}", e))?;
+        Ok(config)
+    }
+
+    pub fn category(&self, class: &str) -> &str {
+        self.categories.get(&class.to_lowercase()).map(|c| c.as_str()).unwrap_or(UNCATEGORIZED)
+    }
+
+    /// Whether `class` or its category is listed in `focus.distracting`.
+    pub fn distracting(&self, class: &str) -> bool {
+        self.listed(&self.focus.distracting, class)
+    }
+
+    /// Whether `class` or its category is listed in `focus.chat`.
+    pub fn chat(&self, class: &str) -> bool {
+        self.listed(&self.focus.chat, class)
+    }
+
+    fn listed(&self, list: &[String], class: &str) -> bool {
+        let class = class.to_lowercase();
+        let category = self.category(&class).to_lowercase();
+        list.iter().any(|d| *d == class || *d == category)
+    }
+}
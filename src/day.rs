@@ -0,0 +1,197 @@
+use chrono::prelude::*;
+use chrono::{Duration, LocalResult};
+
+// All UTC offsets in use are multiples of 15 minutes, so stepping through a
+// day at this resolution finds every local hour boundary and DST transition.
+const STEP: i64 = 15 * 60;
+
+/// Maps a local wall clock time to an instant in `tz`.
+///
+/// Ambiguous times (the repeated hour when clocks fall back) resolve to the
+/// earlier instant, nonexistent times (the skipped hour when clocks spring
+/// forward) resolve to the first valid instant after them.
+pub fn resolve_local<T: TimeZone>(tz: &T, naive: NaiveDateTime) -> DateTime<T> {
+    let mut naive = naive;
+    loop {
+        match tz.from_local_datetime(&naive) {
+            LocalResult::Single(dt) => return dt,
+            LocalResult::Ambiguous(earliest, _) => return earliest,
+            LocalResult::None => naive += Duration::minutes(1),
+        }
+    }
+}
+
+/// Parses a date in the format YYYY-MM-DD.
+pub fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|e| format!("Invalid date {}, must be of format YYYY-MM-DD: {}", s, e))
+}
+
+/// A local calendar day, which is not necessarily 24 hours long.
+#[derive(Debug, Clone)]
+pub struct Day<T: TimeZone> {
+    pub date: NaiveDate,
+    pub start: DateTime<T>,
+    pub end: DateTime<T>,
+}
+
+impl<T: TimeZone> Day<T> {
+    pub fn new(tz: &T, date: NaiveDate) -> Day<T> {
+        let next = date.succ_opt().unwrap();
+        Day {
+            date,
+            start: resolve_local(tz, date.and_hms_opt(0, 0, 0).unwrap()),
+            end: resolve_local(tz, next.and_hms_opt(0, 0, 0).unwrap()),
+        }
+    }
+
+    /// Length of the day in seconds, 23 or 25 hours on most DST transition days.
+    pub fn seconds(&self) -> i64 {
+        self.end.timestamp() - self.start.timestamp()
+    }
+
+    pub fn contains(&self, ts: i64) -> bool {
+        ts >= self.start.timestamp() && ts < self.end.timestamp()
+    }
+
+    /// Seconds elapsed between the start of the day and `ts`.
+    pub fn offset(&self, ts: i64) -> i64 {
+        ts - self.start.timestamp()
+    }
+
+    /// Offsets of every full local hour within the day along with the hour
+    /// shown on the wall clock at that point.
+    pub fn hour_marks(&self) -> Vec<(i64, u32)> {
+        let tz = self.start.timezone();
+        let mut marks = vec![];
+        let mut offset = 0;
+        while offset < self.seconds() {
+            let local = tz.timestamp_opt(self.start.timestamp() + offset, 0).unwrap();
+            if local.minute() == 0 {
+                marks.push((offset, local.hour()));
+            }
+            offset += STEP;
+        }
+        marks
+    }
+
+    /// The wall clock time `offset` seconds after the start of the day like
+    /// `3:00`, the end of the day and times after it count on from `24:00`.
+    pub fn clock(&self, offset: i64) -> String {
+        let local = self.start.timezone().timestamp_opt(self.start.timestamp() + offset, 0).unwrap();
+        let hour = local.hour() as i64 + 24 * (local.date_naive() - self.date).num_days();
+        format!("{}:{:02}", hour, local.minute())
+    }
+
+    /// Offsets within the day at which the UTC offset changes.
+    pub fn transitions(&self) -> Vec<i64> {
+        let tz = self.start.timezone();
+        let mut transitions = vec![];
+        let mut last = self.start.offset().fix();
+        let mut offset = STEP;
+        while offset < self.seconds() {
+            let current = tz.timestamp_opt(self.start.timestamp() + offset, 0).unwrap().offset().fix();
+            if current != last {
+                transitions.push(offset);
+                last = current;
+            }
+            offset += STEP;
+        }
+        transitions
+    }
+}
+
+/// All local days from `first` to `last`, both inclusive.
+pub fn days<T: TimeZone>(tz: &T, first: NaiveDate, last: NaiveDate) -> Vec<Day<T>> {
+    first.iter_days().take_while(|d| *d <= last).map(|d| Day::new(tz, d)).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::{New_York, Santiago};
+    use chrono_tz::Europe::Berlin;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn regular_day_is_24_hours() {
+        let day = Day::new(&Berlin, date(2023, 6, 1));
+        assert_eq!(day.seconds(), 24 * 3600);
+        assert_eq!(day.hour_marks().len(), 24);
+        assert!(day.transitions().is_empty());
+    }
+
+    #[test]
+    fn spring_forward_day_is_23_hours() {
+        let day = Day::new(&Berlin, date(2023, 3, 26));
+        assert_eq!(day.seconds(), 23 * 3600);
+
+        let hours: Vec<u32> = day.hour_marks().iter().map(|(_, h)| *h).collect();
+        assert_eq!(hours.len(), 23);
+        assert!(!hours.contains(&2));
+        assert_eq!(day.transitions(), vec![2 * 3600]);
+
+        // 03:30 CEST happens 2.5 hours after midnight
+        let ts = Berlin.with_ymd_and_hms(2023, 3, 26, 3, 30, 0).unwrap().timestamp();
+        assert_eq!(day.offset(ts), 2 * 3600 + 1800);
+    }
+
+    #[test]
+    fn fall_back_day_is_25_hours() {
+        let day = Day::new(&New_York, date(2023, 11, 5));
+        assert_eq!(day.seconds(), 25 * 3600);
+
+        let hours: Vec<u32> = day.hour_marks().iter().map(|(_, h)| *h).collect();
+        assert_eq!(hours.len(), 25);
+        assert_eq!(hours.iter().filter(|h| **h == 1).count(), 2);
+        assert_eq!(day.transitions(), vec![2 * 3600]);
+
+        // both 01:30 samples land in separate places instead of overlapping
+        let (first, second) = match New_York.with_ymd_and_hms(2023, 11, 5, 1, 30, 0) {
+            LocalResult::Ambiguous(a, b) => (a.timestamp(), b.timestamp()),
+            _ => panic!("01:30 should be ambiguous"),
+        };
+        assert_eq!(day.offset(first), 3600 + 1800);
+        assert_eq!(day.offset(second), 2 * 3600 + 1800);
+        assert!(day.contains(second));
+    }
+
+    #[test]
+    fn nonexistent_midnight_starts_day_at_first_valid_time() {
+        // Chile springs forward at midnight, so 2022-09-11 starts at 01:00
+        let day = Day::new(&Santiago, date(2022, 9, 11));
+        assert_eq!(day.start.hour(), 1);
+        assert_eq!(day.seconds(), 23 * 3600);
+    }
+
+    #[test]
+    fn clock_shows_wall_clock_time() {
+        let day = Day::new(&Berlin, date(2023, 6, 1));
+        assert_eq!(day.clock(2 * 3600 + 1800), "2:30");
+        assert_eq!(day.clock(24 * 3600), "24:00");
+
+        // the hour after 01:00 is 03:00 when clocks spring forward
+        let day = Day::new(&Berlin, date(2023, 3, 26));
+        assert_eq!(day.clock(2 * 3600), "3:00");
+        assert_eq!(day.clock(23 * 3600), "24:00");
+        assert_eq!(day.clock(24 * 3600), "25:00");
+
+        let day = Day::new(&New_York, date(2023, 11, 5));
+        assert_eq!(day.clock(2 * 3600), "1:00");
+        assert_eq!(day.clock(25 * 3600), "24:00");
+
+        assert_eq!(Day::new(&Santiago, date(2022, 9, 11)).clock(0), "1:00");
+    }
+
+    #[test]
+    fn consecutive_days_share_boundaries() {
+        let days = days(&Berlin, date(2023, 10, 28), date(2023, 10, 30));
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[0].end, days[1].start);
+        assert_eq!(days[1].end, days[2].start);
+        assert_eq!(days[1].seconds(), 25 * 3600);
+    }
+}
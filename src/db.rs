@@ -0,0 +1,451 @@
+use rusqlite::{Connection, OpenFlags};
+use crate::edits;
+use crate::spans::Span;
+use std::env;
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "sqlcipher")]
+use std::io::Read;
+#[cfg(feature = "sqlcipher")]
+use std::process::Command;
+#[cfg(feature = "sqlcipher")]
+use std::sync::OnceLock;
+
+/// Seconds between two samples taken by the `tracking` daemon.
+pub const SAMPLE_INTERVAL: i64 = 10;
+
+/// Incremented whenever `migrate` needs to change existing databases.
+const SCHEMA_VERSION: i64 = 1;
+
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub class: String,
+    pub title: String,
+    pub idle: bool,
+    pub ts: i64,
+    /// The machine the sample was recorded on, empty if unknown.
+    pub host: String,
+}
+
+/// Selects the samples to load, `start <= ts < end`.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub start: i64,
+    pub end: i64,
+    /// Include idle samples.
+    pub idle: bool,
+    /// Only include samples of this machine.
+    pub host: Option<String>,
+}
+
+/// A sample of an active window without title, shared by the tests.
+#[cfg(test)]
+pub(crate) fn sample(host: &str, class: &str, ts: i64) -> Sample {
+    Sample { class: class.to_string(), title: String::new(), idle: false, ts, host: host.to_string() }
+}
+
+/// Resolves the database path from the `-d` flag, the `TRACKING_DB`
+/// environment variable or the `tracking.db` fallback, in that order.
+pub fn path(arg: Option<String>) -> String {
+    if let Some(d) = arg {
+        d
+    } else if let Ok(s) = env::var("TRACKING_DB") {
+        s
+    } else {
+        "tracking.db".to_string()
+    }
+}
+
+/// Opens the database and brings its tables up to date, for programs that
+/// write to it.
+pub fn open(path: &str) -> rusqlite::Result<Connection> {
+    let conn = connect(path, OpenFlags::default())?;
+    create_tables(&conn)?;
+    Ok(conn)
+}
+
+/// Opens the database without touching its tables, unlocking it if it is
+/// encrypted.
+pub fn connect<P: AsRef<Path>>(path: P, flags: OpenFlags) -> rusqlite::Result<Connection> {
+    let conn = Connection::open_with_flags(&path, flags)?;
+    unlock(&conn, path.as_ref())?;
+    Ok(conn)
+}
+
+fn key_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE), Some(message))
+}
+
+/// Whether `path` is an existing unencrypted database, encrypted ones don't
+/// start with the SQLite header.
+#[cfg(feature = "sqlcipher")]
+fn is_plain(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    fs::File::open(path).and_then(|mut f| f.read_exact(&mut header)).is_ok() && &header == b"SQLite format 3\0"
+}
+
+/// The key of an encrypted database, taken from `TRACKING_KEY`, the file
+/// named by `TRACKING_KEY_FILE` or, if `keyring` is set, the keyring entry
+/// stored with `secret-tool store --label=tracking application tracking`, in
+/// that order. The keyring is only asked once per process.
+#[cfg(feature = "sqlcipher")]
+pub fn key(keyring: bool) -> rusqlite::Result<Option<String>> {
+    if let Ok(k) = env::var("TRACKING_KEY") {
+        return Ok(Some(k));
+    }
+    if let Ok(f) = env::var("TRACKING_KEY_FILE") {
+        let k = fs::read_to_string(&f).map_err(|e| key_error(format!("Could not read key file {}: {}", f, e)))?;
+        return Ok(Some(k.trim_end_matches('\n').to_string()));
+    }
+    if !keyring {
+        return Ok(None);
+    }
+    static KEYRING: OnceLock<Option<String>> = OnceLock::new();
+    Ok(KEYRING.get_or_init(|| {
+        let lookup = Command::new("secret-tool").args(["lookup", "application", "tracking"]).output();
+        match lookup {
+            Ok(out) if out.status.success() && !out.stdout.is_empty() => Some(String::from_utf8_lossy(&out.stdout).to_string()),
+            _ => None,
+        }
+    }).clone())
+}
+
+/// Sets the key of encrypted and new databases, plain ones such as merge
+/// sources or old backups are opened without one.
+#[cfg(feature = "sqlcipher")]
+fn unlock(conn: &Connection, path: &Path) -> rusqlite::Result<()> {
+    if is_plain(path) {
+        return Ok(());
+    }
+    if let Some(key) = key(true)? {
+        conn.pragma_update(None, "key", key)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+fn unlock(_conn: &Connection, _path: &Path) -> rusqlite::Result<()> {
+    // refuse to silently write titles in plain text when encryption was asked for
+    if env::var("TRACKING_KEY").is_ok() || env::var("TRACKING_KEY_FILE").is_ok() {
+        return Err(key_error("A database key is set but encryption support was not compiled in, build with --features sqlcipher".to_string()));
+    }
+    Ok(())
+}
+
+/// Names of the tables and the statements creating them in the current schema.
+const TABLES: [(&str, &str); 6] = [
+    ("tracking", "create table if not exists tracking (
+            id integer primary key,
+            class text not null,
+            title text not null,
+            idle integer not null,
+            ts integer not null,
+            host text not null default '',
+            unique (host, ts)
+    );"),
+    // samples older than the retention window, merged into spans by `compact`
+    ("compacted", "create table if not exists compacted (
+            id integer primary key,
+            class text not null,
+            title text not null,
+            idle integer not null,
+            host text not null default '',
+            start integer not null,
+            end integer not null
+    );"),
+    // manual corrections, see `edits`
+    ("edits", "create table if not exists edits (
+            id integer primary key,
+            kind text not null,
+            start integer not null,
+            end integer not null,
+            class text not null default '',
+            title text not null default '',
+            host text not null default ''
+    );"),
+    // sessions started with `trackingctl focus`, see `focus`
+    ("focus_sessions", "create table if not exists focus_sessions (
+            id integer primary key,
+            start integer not null,
+            end integer not null,
+            ended integer,
+            host text not null default '',
+            label text not null default '',
+            focused integer not null default 0,
+            distracted integer not null default 0,
+            distractions integer not null default 0,
+            classes text not null default '{}'
+    );"),
+    // tags and notes on time ranges, see `annotations`
+    ("annotations", "create table if not exists annotations (
+            id integer primary key,
+            start integer not null,
+            end integer not null,
+            tag text not null,
+            note text not null default ''
+    );"),
+    // when the daemon was running, see `runs`
+    ("runs", "create table if not exists runs (
+            id integer primary key,
+            host text not null,
+            start integer not null,
+            last_seen integer not null,
+            stopped integer,
+            unique(host, start)
+    );"),
+];
+
+/// Creates missing tables and migrates older databases, only programs that
+/// write to the database do this.
+pub fn create_tables(conn: &Connection) -> rusqlite::Result<()> {
+    for (_, sql) in TABLES {
+        conn.execute(sql, ())?;
+    }
+    migrate(conn)?;
+    conn.execute("create index if not exists tracking_ts on tracking (ts);", ())?;
+    conn.execute("create index if not exists compacted_start on compacted (start);", ())?;
+    Ok(())
+}
+
+fn has_table(conn: &Connection, name: &str) -> rusqlite::Result<bool> {
+    conn.query_row("select count(*) > 0 from main.sqlite_master where type = 'table' and name = ?1;", [name], |row| row.get(0))
+}
+
+/// Opens the database for reading without changing it, e.g. while the daemon
+/// is writing to it. Tables an older version didn't create yet are read as
+/// empty ones and samples of databases without hosts have an empty one.
+pub fn open_read_only(path: &str) -> rusqlite::Result<Connection> {
+    let conn = connect(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    // temporary tables and views live in memory and shadow the missing ones
+    for (name, sql) in TABLES {
+        if !has_table(&conn, name)? {
+            conn.execute(&sql.replacen("create table if not exists", "create temp table", 1), ())?;
+        }
+    }
+    let has_host = || -> rusqlite::Result<bool> {
+        Ok(conn.prepare("select * from main.tracking limit 0;")?.column_names().contains(&"host"))
+    };
+    if has_table(&conn, "tracking")? && !has_host()? {
+        conn.execute("create temp view tracking as select id, class, title, idle, ts, '' as host from main.tracking;", ())?;
+    }
+    Ok(conn)
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.query_row("pragma user_version;", (), |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    if version < 1 {
+        // timestamps used to be unique, samples from several machines need
+        // them to be unique per host, which requires recreating the table
+        let has_host = tx.prepare("select * from tracking limit 0;")?.column_names().contains(&"host");
+        if !has_host {
+            tx.execute_batch(
+                "create table tracking_new (
+                    id integer primary key,
+                    class text not null,
+                    title text not null,
+                    idle integer not null,
+                    ts integer not null,
+                    host text not null default '',
+                    unique (host, ts)
+                );
+                insert into tracking_new (id, class, title, idle, ts) select id, class, title, idle, ts from tracking;
+                drop table tracking;
+                alter table tracking_new rename to tracking;",
+            )?;
+        }
+        // samples recorded before hosts were stored can only come from this
+        // machine, if it has one at the same time already that one is kept
+        let host = hostname();
+        tx.execute("delete from tracking where host = '' and ts in (select ts from tracking where host = ?1);", [&host])?;
+        tx.execute("update tracking set host = ?1 where host = '';", [&host])?;
+    }
+
+    tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    tx.commit()
+}
+
+/// All samples matching `filter` in chronological order. Compacted spans are
+/// turned back into samples, so callers see the same data whether it has been
+/// compacted or not, and manual edits are applied.
+pub fn samples(conn: &Connection, filter: &Filter) -> rusqlite::Result<Vec<Sample>> {
+    let edits = edits::load(conn, filter.start, filter.end, &filter.host)?;
+    // edits can mark idle samples active, so those are needed as well
+    let unedited = if edits.is_empty() { filter.clone() } else { Filter { idle: true, ..filter.clone() } };
+    let mut samples = raw_samples(conn, &unedited)?;
+    let compacted = compacted(conn, &unedited)?;
+    if !compacted.is_empty() {
+        samples.extend(compacted.iter().flat_map(expand).filter(|s| s.ts >= filter.start && s.ts < filter.end));
+        samples.sort_by_key(|s| s.ts);
+    }
+    if edits.is_empty() {
+        return Ok(samples);
+    }
+    Ok(edits::apply(samples, &edits, filter))
+}
+
+/// Samples matching `filter` that have not been compacted yet.
+pub fn raw_samples(conn: &Connection, filter: &Filter) -> rusqlite::Result<Vec<Sample>> {
+    let query = if filter.idle {
+        "select class, title, idle, ts, host FROM tracking where ts >= ?1 and ts < ?2 and (?3 is null or host = ?3) order by ts asc;"
+    } else {
+        "select class, title, idle, ts, host FROM tracking where ts >= ?1 and ts < ?2 and (?3 is null or host = ?3) and idle not like 1 order by ts asc;"
+    };
+    let mut stmt = conn.prepare(query)?;
+    let rows = stmt.query_map((filter.start, filter.end, &filter.host), |row| {
+        Ok(Sample {
+            class: row.get(0)?,
+            title: row.get(1)?,
+            idle:  row.get::<_, i64>(2)? == 1,
+            ts:    row.get(3)?,
+            host:  row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Compacted spans overlapping the range of `filter`.
+pub fn compacted(conn: &Connection, filter: &Filter) -> rusqlite::Result<Vec<Span>> {
+    let mut stmt = conn.prepare(
+        "select class, title, idle, host, start, end FROM compacted where end > ?1 and start < ?2
+        and (?3 is null or host = ?3) and (?4 or idle not like 1) order by start asc;"
+    )?;
+    let rows = stmt.query_map((filter.start, filter.end, &filter.host, filter.idle), |row| {
+        Ok(Span {
+            class: row.get(0)?,
+            title: row.get(1)?,
+            idle:  row.get::<_, i64>(2)? == 1,
+            host:  row.get(3)?,
+            start: row.get(4)?,
+            end:   row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn insert_compacted(conn: &Connection, span: &Span) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare_cached("INSERT INTO compacted (class, title, idle, host, start, end) values (?1, ?2, ?3, ?4, ?5, ?6);")?;
+    stmt.execute((&span.class, &span.title, span.idle as i64, &span.host, span.start, span.end))?;
+    Ok(())
+}
+
+/// Inserts a compacted span copied from another database unless the same one
+/// is present already, returns whether it was inserted.
+pub fn insert_compacted_new(conn: &Connection, span: &Span) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO compacted (class, title, idle, host, start, end) select ?1, ?2, ?3, ?4, ?5, ?6
+        where not exists (select 1 from compacted where host = ?4 and start = ?5 and end = ?6 and class = ?1 and title = ?2);"
+    )?;
+    Ok(stmt.execute((&span.class, &span.title, span.idle as i64, &span.host, span.start, span.end))? > 0)
+}
+
+/// Samples every `SAMPLE_INTERVAL` seconds covering `span`.
+pub fn expand(span: &Span) -> Vec<Sample> {
+    let count = ((span.seconds() + SAMPLE_INTERVAL / 2) / SAMPLE_INTERVAL).max(1);
+    (0..count).map(|i| Sample {
+        class: span.class.clone(),
+        title: span.title.clone(),
+        idle: span.idle,
+        ts: span.start + i * SAMPLE_INTERVAL,
+        host: span.host.clone(),
+    }).collect()
+}
+
+/// Inserts `sample` unless there already is a sample of the same machine at
+/// the same time, returns whether it was inserted.
+pub fn insert_new(conn: &Connection, sample: &Sample) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare_cached("INSERT OR IGNORE INTO tracking (class, title, idle, ts, host) values (?1, ?2, ?3, ?4, ?5);")?;
+    let changed = stmt.execute((&sample.class, &sample.title, sample.idle as i64, sample.ts, &sample.host))?;
+    Ok(changed > 0)
+}
+
+/// All machines that recorded samples.
+pub fn hosts(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("select host from tracking union select host from compacted order by host;")?;
+    let rows = stmt.query_map((), |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Name of this machine, taken from `HOSTNAME` or `/etc/hostname`.
+pub fn hostname() -> String {
+    if let Ok(h) = env::var("HOSTNAME") {
+        return h;
+    }
+    fs::read_to_string("/etc/hostname").map(|h| h.trim().to_string()).unwrap_or_else(|_| "localhost".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The table of databases recorded before hosts were stored.
+    const OLD_TRACKING: &str = "create table tracking (
+            id integer primary key,
+            class text not null,
+            title text not null,
+            idle integer not null,
+            ts integer not null unique
+    );";
+
+    fn hosts(conn: &Connection) -> Vec<(String, i64, String)> {
+        let filter = Filter { start: 0, end: 100, idle: true, host: None };
+        let mut samples: Vec<(String, i64, String)> = raw_samples(conn, &filter).unwrap().into_iter().map(|s| (s.host, s.ts, s.class)).collect();
+        samples.sort();
+        samples
+    }
+
+    #[test]
+    fn migration_stores_this_host() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(OLD_TRACKING, ()).unwrap();
+        conn.execute("insert into tracking (class, title, idle, ts) values ('vim', '', 0, 0), ('vim', '', 0, 10);", ()).unwrap();
+        create_tables(&conn).unwrap();
+        assert_eq!(hosts(&conn), vec![(hostname(), 0, "vim".to_string()), (hostname(), 10, "vim".to_string())]);
+
+        // other machines may record at the same time now
+        assert!(insert_new(&conn, &sample("other", "xterm", 0)).unwrap());
+        assert!(!insert_new(&conn, &sample(&hostname(), "xterm", 0)).unwrap());
+        create_tables(&conn).unwrap();
+        assert_eq!(hosts(&conn).len(), 3);
+    }
+
+    #[test]
+    fn migration_keeps_one_sample_per_time() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(TABLES[0].1, ()).unwrap();
+        for s in [sample("", "vim", 0), sample("", "vim", 10), sample(&hostname(), "xterm", 0)] {
+            insert_new(&conn, &s).unwrap();
+        }
+        create_tables(&conn).unwrap();
+        assert_eq!(hosts(&conn), vec![(hostname(), 0, "xterm".to_string()), (hostname(), 10, "vim".to_string())]);
+    }
+
+    #[test]
+    fn read_only_leaves_old_databases_alone() {
+        let path = env::temp_dir().join(format!("tracking-read-only-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute(OLD_TRACKING, ()).unwrap();
+            conn.execute("insert into tracking (class, title, idle, ts) values ('vim', '', 0, 0);", ()).unwrap();
+        }
+
+        let conn = open_read_only(path.to_str().unwrap()).unwrap();
+        let filter = Filter { start: 0, end: 100, idle: false, host: None };
+        let read: Vec<(String, String)> = samples(&conn, &filter).unwrap().into_iter().map(|s| (s.class, s.host)).collect();
+        assert_eq!(read, vec![("vim".to_string(), String::new())]);
+        assert!(insert_new(&conn, &sample("", "xterm", 10)).is_err());
+        drop(conn);
+
+        let conn = Connection::open(&path).unwrap();
+        let version: i64 = conn.query_row("pragma user_version;", (), |row| row.get(0)).unwrap();
+        assert_eq!(version, 0);
+        assert!(!has_table(&conn, "edits").unwrap());
+        drop(conn);
+        fs::remove_file(&path).unwrap();
+    }
+}
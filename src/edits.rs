@@ -0,0 +1,230 @@
+use rusqlite::Connection;
+use crate::db::{Filter, Sample, SAMPLE_INTERVAL};
+
+/// What an edit does to the samples within its range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// Replaces the samples by time spent in `class` and `title`, e.g. in a
+    /// meeting away from the computer.
+    Add,
+    /// Changes the class and title of the samples, empty ones are kept.
+    Relabel,
+    /// Marks the samples idle.
+    Idle,
+    /// Marks the samples as not idle.
+    Active,
+    /// Removes the samples.
+    Delete,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Add => "add",
+            Kind::Relabel => "relabel",
+            Kind::Idle => "idle",
+            Kind::Active => "active",
+            Kind::Delete => "delete",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Kind> {
+        [Kind::Add, Kind::Relabel, Kind::Idle, Kind::Active, Kind::Delete].into_iter().find(|k| k.name() == name)
+    }
+}
+
+/// A manual correction of the samples `start <= ts < end`. Edits are kept
+/// apart from the samples and applied whenever samples are read, so they are
+/// not lost when the samples are compacted, scrubbed or merged again.
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub id: i64,
+    pub kind: Kind,
+    pub start: i64,
+    pub end: i64,
+    pub class: String,
+    pub title: String,
+    /// Only samples of this machine are changed, all machines if empty.
+    pub host: String,
+}
+
+impl Edit {
+    fn applies_to(&self, sample: &Sample) -> bool {
+        sample.ts >= self.start && sample.ts < self.end && (self.host.is_empty() || self.host == sample.host)
+    }
+}
+
+pub fn insert(conn: &Connection, edit: &Edit) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO edits (kind, start, end, class, title, host) values (?1, ?2, ?3, ?4, ?5, ?6);",
+        (edit.kind.name(), edit.start, edit.end, &edit.class, &edit.title, &edit.host),
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Removes an edit, returns whether it existed.
+pub fn remove(conn: &Connection, id: i64) -> rusqlite::Result<bool> {
+    Ok(conn.execute("delete from edits where id = ?1;", [id])? > 0)
+}
+
+/// Removes the part of `edit` within `start <= t < end` once it has been
+/// applied to the samples for good, an edit reaching past both ends is split.
+pub fn cut(conn: &Connection, edit: &Edit, start: i64, end: i64) -> rusqlite::Result<()> {
+    if edit.end <= start || edit.start >= end {
+        return Ok(());
+    }
+    if edit.start < start && edit.end > end {
+        insert(conn, &Edit { start: end, ..edit.clone() })?;
+    }
+    if edit.start < start {
+        conn.execute("update edits set end = ?1 where id = ?2;", (start, edit.id))?;
+    } else if edit.end > end {
+        conn.execute("update edits set start = ?1 where id = ?2;", (end, edit.id))?;
+    } else {
+        remove(conn, edit.id)?;
+    }
+    Ok(())
+}
+
+/// Edits overlapping `start <= t < end` in the order they were made.
+pub fn load(conn: &Connection, start: i64, end: i64, host: &Option<String>) -> rusqlite::Result<Vec<Edit>> {
+    let mut stmt = conn.prepare(
+        "select id, kind, start, end, class, title, host from edits where end > ?1 and start < ?2
+        and (?3 is null or host = '' or host = ?3) order by id asc;"
+    )?;
+    let rows = stmt.query_map((start, end, host), |row| {
+        let kind: String = row.get(1)?;
+        Ok(Edit {
+            id: row.get(0)?,
+            kind: Kind::from_name(&kind).ok_or_else(|| rusqlite::Error::InvalidColumnType(1, kind, rusqlite::types::Type::Text))?,
+            start: row.get(2)?,
+            end: row.get(3)?,
+            class: row.get(4)?,
+            title: row.get(5)?,
+            host: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Applies `edits` to `samples` one after another, which need to include idle
+/// samples since edits may mark them active. Returns the samples in
+/// chronological order.
+pub fn apply(mut samples: Vec<Sample>, edits: &[Edit], filter: &Filter) -> Vec<Sample> {
+    for edit in edits {
+        match edit.kind {
+            Kind::Add => {
+                samples.retain(|s| !edit.applies_to(s));
+                let mut ts = edit.start;
+                while ts < edit.end {
+                    if ts >= filter.start && ts < filter.end {
+                        samples.push(Sample {
+                            class: edit.class.clone(),
+                            title: edit.title.clone(),
+                            idle: false,
+                            ts,
+                            host: edit.host.clone(),
+                        });
+                    }
+                    ts += SAMPLE_INTERVAL;
+                }
+            },
+            Kind::Delete => samples.retain(|s| !edit.applies_to(s)),
+            Kind::Relabel | Kind::Idle | Kind::Active => {
+                for s in samples.iter_mut().filter(|s| edit.applies_to(s)) {
+                    match edit.kind {
+                        Kind::Idle => s.idle = true,
+                        Kind::Active => s.idle = false,
+                        _ => {
+                            if !edit.class.is_empty() {
+                                s.class = edit.class.clone();
+                            }
+                            if !edit.title.is_empty() {
+                                s.title = edit.title.clone();
+                            }
+                        },
+                    }
+                }
+            },
+        }
+    }
+    if !filter.idle {
+        samples.retain(|s| !s.idle);
+    }
+    samples.sort_by_key(|s| s.ts);
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, sample};
+
+    fn edit(kind: Kind, start: i64, end: i64, class: &str) -> Edit {
+        Edit { id: 0, kind, start, end, class: class.to_string(), title: String::new(), host: String::new() }
+    }
+
+    fn filter(idle: bool) -> Filter {
+        Filter { start: 0, end: 100, idle, host: None }
+    }
+
+    fn classes(samples: &[Sample]) -> Vec<(i64, &str)> {
+        samples.iter().map(|s| (s.ts, s.class.as_str())).collect()
+    }
+
+    #[test]
+    fn later_edits_win() {
+        let samples = vec![sample("a", "x", 0), sample("a", "x", 10)];
+        let edits = vec![edit(Kind::Relabel, 0, 20, "y"), edit(Kind::Relabel, 10, 20, "z")];
+        assert_eq!(classes(&apply(samples.clone(), &edits, &filter(false))), vec![(0, "y"), (10, "z")]);
+
+        // deleting first leaves nothing to relabel, relabeling first is undone by the delete
+        let edits = vec![edit(Kind::Delete, 0, 10, ""), edit(Kind::Relabel, 0, 20, "y")];
+        assert_eq!(classes(&apply(samples.clone(), &edits, &filter(false))), vec![(10, "y")]);
+        let edits = vec![edit(Kind::Relabel, 0, 20, "y"), edit(Kind::Delete, 0, 10, "")];
+        assert_eq!(classes(&apply(samples, &edits, &filter(false))), vec![(10, "y")]);
+    }
+
+    #[test]
+    fn add_replaces_samples_within_filter() {
+        let samples = vec![sample("a", "x", 0), sample("a", "x", 10), sample("a", "x", 40)];
+        let mut meeting = edit(Kind::Add, 5, 30, "meeting");
+        meeting.host = "a".to_string();
+        let filter = Filter { start: 0, end: 20, idle: false, host: None };
+        let result = apply(samples, &[meeting], &filter);
+        assert_eq!(classes(&result), vec![(0, "x"), (5, "meeting"), (15, "meeting"), (40, "x")]);
+        assert!(result.iter().all(|s| s.host == "a"));
+    }
+
+    #[test]
+    fn idle_and_active_toggle_idle_samples() {
+        let mut samples = vec![sample("a", "x", 0), sample("a", "x", 10)];
+        samples[1].idle = true;
+        let edits = vec![edit(Kind::Active, 10, 20, ""), edit(Kind::Idle, 0, 10, "")];
+        assert_eq!(classes(&apply(samples.clone(), &edits, &filter(false))), vec![(10, "x")]);
+        assert_eq!(apply(samples, &edits, &filter(true)).len(), 2);
+    }
+
+    #[test]
+    fn host_edits_only_change_that_host() {
+        let samples = vec![sample("a", "x", 0), sample("b", "x", 0)];
+        let mut relabel = edit(Kind::Relabel, 0, 10, "y");
+        relabel.host = "b".to_string();
+        let result = apply(samples, &[relabel], &filter(false));
+        let hosts: Vec<(&str, &str)> = result.iter().map(|s| (s.host.as_str(), s.class.as_str())).collect();
+        assert!(hosts.contains(&("a", "x")));
+        assert!(hosts.contains(&("b", "y")));
+    }
+
+    #[test]
+    fn cut_splits_edits_reaching_past_both_ends() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::create_tables(&conn).unwrap();
+        let mut e = edit(Kind::Relabel, 0, 100, "y");
+        e.id = insert(&conn, &e).unwrap();
+        cut(&conn, &e, 40, 60).unwrap();
+
+        let ranges: Vec<(i64, i64)> = load(&conn, 0, 100, &None).unwrap().iter().map(|e| (e.start, e.end)).collect();
+        assert_eq!(ranges, vec![(0, 40), (60, 100)]);
+    }
+}
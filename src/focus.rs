@@ -0,0 +1,215 @@
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::HashMap;
+use crate::config::Config;
+use crate::db::{self, Sample, SAMPLE_INTERVAL};
+
+/// A focus session started with `trackingctl focus`, the daemon finishes it
+/// once `end` has passed and stores the summary.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub id: i64,
+    pub start: i64,
+    /// Planned end of the session.
+    pub end: i64,
+    /// When the session was finished or stopped, `None` while it is running.
+    pub ended: Option<i64>,
+    pub host: String,
+    pub label: String,
+    pub summary: Summary,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    /// Seconds spent in classes that are not distracting.
+    pub focused: i64,
+    /// Seconds spent in distracting classes.
+    pub distracted: i64,
+    /// Number of switches to a distracting class.
+    pub distractions: i64,
+    /// Seconds per class, longest first.
+    pub classes: Vec<(String, i64)>,
+}
+
+/// Sums up the time spent during a session, idle samples don't count.
+pub fn summarize(samples: &[Sample], config: &Config) -> Summary {
+    let mut summary = Summary::default();
+    let mut classes: HashMap<&str, i64> = HashMap::new();
+    let mut was_distracted = false;
+    for s in samples.iter().filter(|s| !s.idle) {
+        let distracted = config.distracting(&s.class);
+        if distracted {
+            summary.distracted += SAMPLE_INTERVAL;
+            if !was_distracted {
+                summary.distractions += 1;
+            }
+        } else {
+            summary.focused += SAMPLE_INTERVAL;
+        }
+        was_distracted = distracted;
+        *classes.entry(&s.class).or_default() += SAMPLE_INTERVAL;
+    }
+    summary.classes = classes.into_iter().map(|(c, secs)| (c.to_string(), secs)).collect();
+    summary.classes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    summary
+}
+
+pub fn start(conn: &Connection, session: &Session) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO focus_sessions (start, end, host, label) values (?1, ?2, ?3, ?4);",
+        (session.start, session.end, &session.host, &session.label),
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Session> {
+    let classes: String = row.get(9)?;
+    let classes = json::parse(&classes).unwrap_or(json::JsonValue::Null);
+    Ok(Session {
+        id: row.get(0)?,
+        start: row.get(1)?,
+        end: row.get(2)?,
+        ended: row.get(3)?,
+        host: row.get(4)?,
+        label: row.get(5)?,
+        summary: Summary {
+            focused: row.get(6)?,
+            distracted: row.get(7)?,
+            distractions: row.get(8)?,
+            classes: classes.entries().map(|(c, secs)| (c.to_string(), secs.as_i64().unwrap_or_default())).collect(),
+        },
+    })
+}
+
+const COLUMNS: &str = "id, start, end, ended, host, label, focused, distracted, distractions, classes";
+
+/// The session currently running on `host`, call `finish_overdue` first so
+/// it isn't one that should have been over already.
+pub fn running(conn: &Connection, host: &str) -> rusqlite::Result<Option<Session>> {
+    conn.query_row(
+        &format!("select {} from focus_sessions where ended is null and host = ?1 order by start desc limit 1;", COLUMNS),
+        [host],
+        from_row,
+    ).optional()
+}
+
+/// Finishes the sessions on `host` whose planned end has passed by `now`,
+/// e.g. because the daemon wasn't running when they were over.
+pub fn finish_overdue(conn: &Connection, host: &str, now: i64, config: &Config) -> rusqlite::Result<Vec<Session>> {
+    let mut stmt = conn.prepare(&format!("select {} from focus_sessions where ended is null and host = ?1 and end <= ?2 order by start asc;", COLUMNS))?;
+    let mut sessions: Vec<Session> = stmt.query_map((host, now), from_row)?.collect::<rusqlite::Result<_>>()?;
+    for session in sessions.iter_mut() {
+        let end = session.end;
+        finish(conn, session, end, config)?;
+    }
+    Ok(sessions)
+}
+
+/// Finished sessions started within `start <= t < end`.
+pub fn load(conn: &Connection, start: i64, end: i64, host: &Option<String>) -> rusqlite::Result<Vec<Session>> {
+    let mut stmt = conn.prepare(&format!(
+        "select {} from focus_sessions where ended is not null and start >= ?1 and start < ?2 and (?3 is null or host = ?3) order by start asc;",
+        COLUMNS,
+    ))?;
+    let rows = stmt.query_map((start, end, host), from_row)?;
+    rows.collect()
+}
+
+/// Ends `session` at `ended` and stores the summary of the samples recorded
+/// during it.
+pub fn finish(conn: &Connection, session: &mut Session, ended: i64, config: &Config) -> rusqlite::Result<()> {
+    let filter = db::Filter { start: session.start, end: ended, idle: true, host: Some(session.host.clone()) };
+    session.summary = summarize(&db::samples(conn, &filter)?, config);
+    session.ended = Some(ended);
+    let mut classes = json::JsonValue::new_object();
+    for (class, secs) in &session.summary.classes {
+        classes[class.as_str()] = (*secs).into();
+    }
+    conn.execute(
+        "update focus_sessions set ended = ?1, focused = ?2, distracted = ?3, distractions = ?4, classes = ?5 where id = ?6;",
+        (ended, session.summary.focused, session.summary.distracted, session.summary.distractions, classes.dump(), session.id),
+    )?;
+    Ok(())
+}
+
+/// Parses durations like `25m`, `1h30m` or `90s`, plain numbers are minutes.
+pub fn parse_duration(s: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid duration {}, must be like 25m, 1h30m or 90s", s);
+    if let Ok(minutes) = s.parse::<i64>() {
+        return if minutes > 0 { Ok(minutes * 60) } else { Err(invalid()) };
+    }
+    let mut secs = 0;
+    let mut number = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n: i64 = number.parse().map_err(|_| invalid())?;
+        secs += n * match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        number.clear();
+    }
+    if !number.is_empty() || secs <= 0 {
+        return Err(invalid());
+    }
+    Ok(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sample;
+
+    fn config() -> Config {
+        Config::from_json(&json::parse(r#"{"focus": {"distracting": ["discord"]}}"#).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("25"), Ok(25 * 60));
+        assert_eq!(parse_duration("25m"), Ok(25 * 60));
+        assert_eq!(parse_duration("1h30m"), Ok(90 * 60));
+        assert_eq!(parse_duration("90s"), Ok(90));
+        assert_eq!(parse_duration("1h0m"), Ok(3600));
+    }
+
+    #[test]
+    fn rejects_malformed_durations() {
+        for s in ["", "0", "-5", "0m", "1x", "h", "30m5", "1.5h", "1 h", "m30"] {
+            assert!(parse_duration(s).is_err(), "{:?} should be invalid", s);
+        }
+    }
+
+    #[test]
+    fn summary_counts_switches_to_distractions() {
+        let mut samples = vec![sample("a", "vim", 0), sample("a", "discord", 10), sample("a", "discord", 20), sample("a", "vim", 30), sample("a", "discord", 40)];
+        samples.push(Sample { idle: true, ..sample("a", "discord", 50) });
+        let summary = summarize(&samples, &config());
+        assert_eq!(summary.focused, 20);
+        assert_eq!(summary.distracted, 30);
+        assert_eq!(summary.distractions, 2);
+        assert_eq!(summary.classes, vec![("discord".to_string(), 30), ("vim".to_string(), 20)]);
+    }
+
+    #[test]
+    fn overdue_sessions_end_at_their_planned_end() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::create_tables(&conn).unwrap();
+        for s in [sample("a", "vim", 0), sample("a", "discord", 10), sample("a", "vim", 20)] {
+            db::insert_new(&conn, &s).unwrap();
+        }
+        let session = Session { start: 0, end: 20, host: "a".to_string(), ..Default::default() };
+        start(&conn, &session).unwrap();
+        start(&conn, &Session { start: 15, end: 100, ..session.clone() }).unwrap();
+
+        let finished = finish_overdue(&conn, "a", 50, &config()).unwrap();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].ended, Some(20));
+        assert_eq!((finished[0].summary.focused, finished[0].summary.distracted), (10, 10));
+        assert_eq!(running(&conn, "a").unwrap().map(|s| s.end), Some(100));
+    }
+}
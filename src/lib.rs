@@ -0,0 +1,14 @@
+pub mod activitywatch;
+pub mod annotations;
+pub mod config;
+pub mod day;
+pub mod db;
+pub mod edits;
+pub mod focus;
+pub mod privacy;
+pub mod runs;
+pub mod spans;
+pub mod switches;
+pub mod table;
+pub mod theme;
+pub mod timesheet;
@@ -0,0 +1,129 @@
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+/// Prefix of hashed titles, so they are not hashed again when scrubbing.
+pub const HASH_PREFIX: &str = "hash:";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Don't store the sample at all.
+    Exclude,
+    /// Store the sample with an empty title.
+    Drop,
+    /// Replace the title by a hash, so identical titles can still be told apart.
+    Hash,
+    /// Only keep the first characters of the title.
+    Truncate(usize),
+}
+
+/// Applies `action` to windows whose class and title match the regexes.
+#[derive(Debug)]
+pub struct Rule {
+    pub class: Option<Regex>,
+    pub title: Option<Regex>,
+    pub action: Action,
+}
+
+#[derive(Debug, Default)]
+pub struct Privacy {
+    pub salt: String,
+    pub rules: Vec<Rule>,
+}
+
+impl Rule {
+    pub fn from_json(obj: &json::JsonValue) -> Result<Rule, String> {
+        let regex = |key: &str| -> Result<Option<Regex>, String> {
+            match obj[key].as_str() {
+                Some(r) => Regex::new(r).map(Some).map_err(|e| format!("Invalid {} regex {}: {}", key, r, e)),
+                None => Ok(None),
+            }
+        };
+        let action = match obj["action"].as_str() {
+            Some("exclude") => Action::Exclude,
+            Some("drop") => Action::Drop,
+            Some("hash") => Action::Hash,
+            Some("truncate") => Action::Truncate(obj["length"].as_usize().ok_or("truncate needs a length")?),
+            a => return Err(format!("Unknown action {:?}, must be one of exclude, drop, hash or truncate", a)),
+        };
+        let rule = Rule { class: regex("class")?, title: regex("title")?, action };
+        if rule.class.is_none() && rule.title.is_none() {
+            return Err("Rules need a class or title regex".to_string());
+        }
+        Ok(rule)
+    }
+
+    fn matches(&self, class: &str, title: &str) -> bool {
+        self.class.as_ref().is_none_or(|r| r.is_match(class)) && self.title.as_ref().is_none_or(|r| r.is_match(title))
+    }
+}
+
+impl Privacy {
+    /// The title to store for a window, `None` if it should not be stored at
+    /// all. Only the first matching rule is applied.
+    pub fn apply(&self, class: &str, title: &str) -> Option<String> {
+        let Some(rule) = self.rules.iter().find(|r| r.matches(class, title)) else {
+            return Some(title.to_string());
+        };
+        match rule.action {
+            Action::Exclude => None,
+            Action::Drop => Some("".to_string()),
+            Action::Hash if title.starts_with(HASH_PREFIX) => Some(title.to_string()),
+            Action::Hash => {
+                let hash = Sha256::digest(format!("{}{}", self.salt, title).as_bytes());
+                let hex: String = hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+                Some(format!("{}{}", HASH_PREFIX, hex))
+            },
+            Action::Truncate(length) => Some(title.chars().take(length).collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn privacy(rules: &str) -> Privacy {
+        let rules = json::parse(rules).unwrap();
+        Privacy { salt: "salt".to_string(), rules: rules.members().map(|r| Rule::from_json(r).unwrap()).collect() }
+    }
+
+    #[test]
+    fn first_matching_rule_is_applied() {
+        let p = privacy(r#"[
+            {"class": "^keepassxc$", "action": "exclude"},
+            {"class": "firefox", "title": "Private", "action": "drop"},
+            {"class": "firefox", "action": "truncate", "length": 5}
+        ]"#);
+        assert_eq!(p.apply("keepassxc", "passwords"), None);
+        assert_eq!(p.apply("firefox", "Private Browsing"), Some("".to_string()));
+        assert_eq!(p.apply("firefox", "Äpfel und Birnen"), Some("Äpfel".to_string()));
+        assert_eq!(p.apply("xterm", "~/src"), Some("~/src".to_string()));
+    }
+
+    #[test]
+    fn hashes_are_salted_and_stable() {
+        let p = privacy(r#"[{"class": "mail", "action": "hash"}]"#);
+        let hashed = p.apply("mail", "Re: invoice").unwrap();
+        assert!(hashed.starts_with(HASH_PREFIX));
+        assert_eq!(hashed.len(), HASH_PREFIX.len() + 16);
+        assert_eq!(p.apply("mail", "Re: invoice").unwrap(), hashed);
+        assert_ne!(p.apply("mail", "Re: offer").unwrap(), hashed);
+        // scrubbing again doesn't hash the hash
+        assert_eq!(p.apply("mail", &hashed).unwrap(), hashed);
+
+        let other = Privacy { salt: "pepper".to_string(), ..privacy(r#"[{"class": "mail", "action": "hash"}]"#) };
+        assert_ne!(other.apply("mail", "Re: invoice").unwrap(), hashed);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for rule in [
+            r#"{"action": "drop"}"#,
+            r#"{"class": "x", "action": "blur"}"#,
+            r#"{"class": "x", "action": "truncate"}"#,
+            r#"{"class": "(", "action": "drop"}"#,
+        ] {
+            assert!(Rule::from_json(&json::parse(rule).unwrap()).is_err(), "{} should be invalid", rule);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use rusqlite::{Connection, OptionalExtension};
+use crate::db::{self, SAMPLE_INTERVAL};
+use crate::spans;
+
+/// A daemon that didn't record anything for longer than this, e.g. while the
+/// machine was suspended, starts a new run.
+pub const MAX_SILENCE: i64 = 60;
+
+/// A stretch of time the daemon was running on `host`. It updates `last_seen`
+/// with every sample and sets `stopped` when it is shut down, runs without
+/// `stopped` crashed, were killed or are still running.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub id: i64,
+    pub host: String,
+    pub start: i64,
+    pub last_seen: i64,
+    pub stopped: Option<i64>,
+}
+
+impl Run {
+    /// End of the time covered by the run.
+    pub fn end(&self) -> i64 {
+        self.stopped.unwrap_or(self.last_seen + SAMPLE_INTERVAL)
+    }
+}
+
+pub fn start(conn: &Connection, host: &str, ts: i64) -> rusqlite::Result<Run> {
+    conn.execute("INSERT INTO runs (host, start, last_seen) values (?1, ?2, ?2);", (host, ts))?;
+    Ok(Run { id: conn.last_insert_rowid(), host: host.to_string(), start: ts, last_seen: ts, stopped: None })
+}
+
+/// Records that the daemon is still running at `ts`, after a longer silence
+/// a new run is started so the time in between counts as no data.
+pub fn beat(conn: &Connection, run: &mut Run, ts: i64) -> rusqlite::Result<()> {
+    if ts - run.last_seen > MAX_SILENCE {
+        *run = start(conn, &run.host, ts)?;
+        return Ok(());
+    }
+    conn.execute("update runs set last_seen = ?1 where id = ?2;", (ts, run.id))?;
+    run.last_seen = ts;
+    Ok(())
+}
+
+pub fn stop(conn: &Connection, run: &mut Run, ts: i64) -> rusqlite::Result<()> {
+    conn.execute("update runs set last_seen = ?1, stopped = ?1 where id = ?2;", (ts, run.id))?;
+    run.last_seen = ts;
+    run.stopped = Some(ts);
+    Ok(())
+}
+
+/// Inserts a run copied from another database or extends the same run if it
+/// was copied before it ended, returns whether anything changed.
+pub fn insert(conn: &Connection, run: &Run) -> rusqlite::Result<bool> {
+    Ok(conn.execute(
+        "INSERT INTO runs (host, start, last_seen, stopped) values (?1, ?2, ?3, ?4)
+            on conflict (host, start) do update set last_seen = max(last_seen, excluded.last_seen), stopped = coalesce(stopped, excluded.stopped)
+            where excluded.last_seen > last_seen or (stopped is null and excluded.stopped is not null);",
+        (&run.host, run.start, run.last_seen, run.stopped),
+    )? > 0)
+}
+
+/// Runs overlapping `start <= t < end` ordered by their start.
+pub fn load(conn: &Connection, start: i64, end: i64, host: &Option<String>) -> rusqlite::Result<Vec<Run>> {
+    let mut stmt = conn.prepare(
+        "select id, host, start, last_seen, stopped from runs
+            where start < ?2 and coalesce(stopped, last_seen + ?3) > ?1 and (?4 is null or host = ?4)
+            order by start asc;",
+    )?;
+    let rows = stmt.query_map((start, end, SAMPLE_INTERVAL, host), |row| {
+        Ok(Run {
+            id: row.get(0)?,
+            host: row.get(1)?,
+            start: row.get(2)?,
+            last_seen: row.get(3)?,
+            stopped: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Start of the first recorded run, there is no telling whether the daemon
+/// was running before it.
+pub fn first(conn: &Connection, host: &Option<String>) -> rusqlite::Result<Option<i64>> {
+    conn.query_row("select min(start) from runs where ?1 is null or host = ?1;", [host], |row| row.get(0)).optional().map(Option::flatten)
+}
+
+/// The ranges within `start <= t < end` that none of `covered` covers.
+fn uncovered(mut covered: Vec<(i64, i64)>, start: i64, end: i64) -> Vec<(i64, i64)> {
+    let mut gaps = vec![];
+    let mut cur = start;
+    covered.sort();
+    for (c_start, c_end) in covered {
+        if c_start > cur && cur < end {
+            gaps.push((cur, c_start.min(end)));
+        }
+        cur = cur.max(c_end);
+    }
+    if cur < end {
+        gaps.push((cur, end));
+    }
+    gaps
+}
+
+/// The ranges within `start <= t < end` that none of `runs` covers.
+pub fn gaps(runs: &[Run], start: i64, end: i64) -> Vec<(i64, i64)> {
+    uncovered(runs.iter().map(|r| (r.start, r.end())).collect(), start, end)
+}
+
+/// The ranges within `start <= t < end` without data because the daemon
+/// wasn't running, only from the first recorded run up to `now`. Time added
+/// with `trackingctl add` or samples merged from a database without runs
+/// count as data.
+pub fn no_data(conn: &Connection, start: i64, end: i64, now: i64, host: &Option<String>) -> rusqlite::Result<Vec<(i64, i64)>> {
+    let Some(first) = first(conn, host)? else {
+        return Ok(vec![]);
+    };
+    let (start, end) = (start.max(first), end.min(now));
+    if start >= end {
+        return Ok(vec![]);
+    }
+    let mut no_data = vec![];
+    for (g_start, g_end) in gaps(&load(conn, start, end, host)?, start, end) {
+        let filter = db::Filter { start: g_start, end: g_end, idle: true, host: host.clone() };
+        let samples = spans::merge_by(&db::samples(conn, &filter)?, |_, _| true);
+        no_data.extend(uncovered(samples.iter().map(|s| (s.start, s.end)).collect(), g_start, g_end));
+    }
+    Ok(no_data)
+}
+
+/// Cuts `gaps` to `start <= t < end`, dropping the ones outside of it.
+pub fn clip(gaps: &[(i64, i64)], start: i64, end: i64) -> Vec<(i64, i64)> {
+    gaps.iter().filter(|g| g.1 > start && g.0 < end).map(|g| (g.0.max(start), g.1.min(end))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edits::{self, Edit, Kind};
+
+    fn run(host: &str, start: i64, last_seen: i64, stopped: Option<i64>) -> Run {
+        Run { id: 0, host: host.to_string(), start, last_seen, stopped }
+    }
+
+    fn conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::create_tables(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn gaps_between_runs() {
+        assert_eq!(gaps(&[], 0, 100), vec![(0, 100)]);
+        // a crashed run lasts one interval past its last sample
+        let runs = vec![run("a", 50, 80, Some(90)), run("a", 10, 20, None)];
+        assert_eq!(gaps(&runs, 0, 100), vec![(0, 10), (30, 50), (90, 100)]);
+        assert_eq!(gaps(&runs, 15, 60), vec![(30, 50)]);
+    }
+
+    #[test]
+    fn added_time_is_no_gap() {
+        let conn = conn();
+        insert(&conn, &run("a", 0, 100, Some(100))).unwrap();
+        insert(&conn, &run("a", 1000, 1100, Some(1100))).unwrap();
+        let meeting = Edit { id: 0, kind: Kind::Add, start: 200, end: 500, class: "meeting".to_string(), title: String::new(), host: "a".to_string() };
+        edits::insert(&conn, &meeting).unwrap();
+        db::insert_new(&conn, &db::sample("b", "vim", 700)).unwrap();
+        assert_eq!(no_data(&conn, 0, 1100, 2000, &None).unwrap(), vec![(100, 200), (500, 700), (710, 1000)]);
+    }
+
+    #[test]
+    fn overlapping_runs_of_hosts_leave_no_gap() {
+        let runs = vec![run("a", 0, 40, Some(40)), run("b", 30, 70, Some(70)), run("a", 10, 20, Some(20))];
+        assert_eq!(gaps(&runs, 0, 100), vec![(70, 100)]);
+        assert!(gaps(&runs, 0, 60).is_empty());
+    }
+
+    #[test]
+    fn beat_after_silence_starts_new_run() {
+        let conn = conn();
+        let mut r = start(&conn, "a", 0).unwrap();
+        beat(&conn, &mut r, MAX_SILENCE).unwrap();
+        beat(&conn, &mut r, 2 * MAX_SILENCE + 1).unwrap();
+        stop(&conn, &mut r, 2 * MAX_SILENCE + 5).unwrap();
+
+        let runs = load(&conn, 0, 1000, &None).unwrap();
+        let ranges: Vec<(i64, i64)> = runs.iter().map(|r| (r.start, r.end())).collect();
+        assert_eq!(ranges, vec![(0, MAX_SILENCE + SAMPLE_INTERVAL), (2 * MAX_SILENCE + 1, 2 * MAX_SILENCE + 5)]);
+    }
+
+    #[test]
+    fn insert_extends_copied_runs_once() {
+        let conn = conn();
+        assert!(insert(&conn, &run("a", 0, 20, None)).unwrap());
+        assert!(!insert(&conn, &run("a", 0, 20, None)).unwrap());
+        assert!(insert(&conn, &run("a", 0, 40, Some(40))).unwrap());
+        assert!(!insert(&conn, &run("a", 0, 30, None)).unwrap());
+        assert_eq!(load(&conn, 0, 100, &None).unwrap(), vec![Run { id: 1, ..run("a", 0, 40, Some(40)) }]);
+    }
+
+    #[test]
+    fn no_data_starts_at_first_run() {
+        let conn = conn();
+        assert!(no_data(&conn, 0, 100, 100, &None).unwrap().is_empty());
+        insert(&conn, &run("a", 20, 40, Some(40))).unwrap();
+        insert(&conn, &run("b", 60, 70, Some(70))).unwrap();
+        assert_eq!(no_data(&conn, 0, 100, 90, &None).unwrap(), vec![(40, 60), (70, 90)]);
+        assert_eq!(no_data(&conn, 0, 100, 90, &Some("b".to_string())).unwrap(), vec![(70, 90)]);
+    }
+}
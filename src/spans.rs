@@ -0,0 +1,202 @@
+use crate::db::{Sample, SAMPLE_INTERVAL};
+
+/// Samples further apart than this are not considered to be continuous.
+pub const MAX_GAP: i64 = 2 * SAMPLE_INTERVAL;
+
+/// A stretch of time `start <= t < end` spent in the same window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub class: String,
+    pub title: String,
+    pub idle: bool,
+    pub host: String,
+    pub start: i64,
+    pub end: i64,
+}
+
+impl Span {
+    pub fn seconds(&self) -> i64 {
+        self.end - self.start
+    }
+}
+
+/// Merges consecutive samples of the same window into spans. Each sample
+/// accounts for one `SAMPLE_INTERVAL`, samples more than `MAX_GAP` apart
+/// always start a new span.
+pub fn merge(samples: &[Sample]) -> Vec<Span> {
+    merge_by(samples, |a, b| a.class == b.class && a.title == b.title && a.idle == b.idle)
+}
+
+/// Like `merge` but only splits spans where `same` returns false for two
+/// consecutive samples, e.g. to merge all titles of a class. Samples of
+/// different machines are merged separately, the resulting spans are ordered
+/// by their start.
+pub fn merge_by<F: Fn(&Sample, &Sample) -> bool>(samples: &[Sample], same: F) -> Vec<Span> {
+    merge_by_gap(samples, MAX_GAP, same)
+}
+
+/// Like `merge_by` but samples up to `max_gap` seconds apart are continuous,
+/// e.g. for samples recorded with a longer interval. Each span lasts until
+/// the next sample if that one is continuous, the last one before a gap as
+/// long as the samples of its host are usually apart.
+pub fn merge_by_gap<F: Fn(&Sample, &Sample) -> bool>(samples: &[Sample], max_gap: i64, same: F) -> Vec<Span> {
+    let mut hosts: Vec<&str> = samples.iter().map(|s| s.host.as_str()).collect();
+    hosts.sort();
+    hosts.dedup();
+    if hosts.len() <= 1 {
+        return merge_host(samples, max_gap, &same);
+    }
+
+    let mut spans = vec![];
+    for host in hosts {
+        let samples: Vec<Sample> = samples.iter().filter(|s| s.host == host).cloned().collect();
+        spans.extend(merge_host(&samples, max_gap, &same));
+    }
+    spans.sort_by_key(|s| s.start);
+    spans
+}
+
+/// The usual distance of continuous samples, `SAMPLE_INTERVAL` if there are
+/// none. The last sample before a gap lasts this long.
+fn interval(samples: &[Sample], max_gap: i64) -> i64 {
+    let mut distances: Vec<i64> = samples.windows(2).map(|w| w[1].ts - w[0].ts).filter(|d| *d > 0 && *d <= max_gap).collect();
+    distances.sort();
+    distances.get(distances.len() / 2).copied().unwrap_or(SAMPLE_INTERVAL)
+}
+
+fn merge_host<F: Fn(&Sample, &Sample) -> bool>(samples: &[Sample], max_gap: i64, same: &F) -> Vec<Span> {
+    let interval = interval(samples, max_gap);
+    let mut spans: Vec<Span> = vec![];
+    let mut last: Option<&Sample> = None;
+    for sample in samples {
+        let end = sample.ts + interval;
+        if let (Some(prev), Some(span)) = (last, spans.last_mut()) {
+            let continuous = sample.ts - prev.ts <= max_gap;
+            if same(prev, sample) && continuous {
+                span.end = end;
+                last = Some(sample);
+                continue;
+            }
+            // samples may be taken slightly early, don't let spans overlap
+            span.end = if continuous { sample.ts } else { span.end.min(sample.ts) };
+        }
+        spans.push(Span {
+            class: sample.class.clone(),
+            title: sample.title.clone(),
+            idle: sample.idle,
+            host: sample.host.clone(),
+            start: sample.ts,
+            end,
+        });
+        last = Some(sample);
+    }
+    spans
+}
+
+/// Joins the spans for which `same` returns true that overlap or touch, e.g.
+/// of several machines used at the same time, so time is only counted once.
+/// Joined spans keep the class, title and host of the first one, the result
+/// is ordered by start.
+pub fn union<F: Fn(&Span, &Span) -> bool>(spans: &[Span], same: F) -> Vec<Span> {
+    let mut sorted: Vec<&Span> = spans.iter().collect();
+    sorted.sort_by_key(|s| s.start);
+    let mut joined: Vec<Span> = vec![];
+    for span in sorted {
+        // the last joined span of a kind ends last since overlapping ones are joined
+        match joined.iter_mut().rev().find(|j| same(j, span)) {
+            Some(j) if j.end >= span.start => j.end = j.end.max(span.end),
+            _ => joined.push(span.clone()),
+        }
+    }
+    joined
+}
+
+/// Cuts `spans` down to the part that lies within `start <= t < end`.
+pub fn clip(spans: &[Span], start: i64, end: i64) -> Vec<Span> {
+    spans.iter().filter(|s| s.end > start && s.start < end).map(|s| {
+        let mut s = s.clone();
+        s.start = s.start.max(start);
+        s.end = s.end.min(end);
+        s
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sample;
+
+    fn ranges(spans: &[Span]) -> Vec<(&str, &str, i64, i64)> {
+        spans.iter().map(|s| (s.host.as_str(), s.class.as_str(), s.start, s.end)).collect()
+    }
+
+    #[test]
+    fn consecutive_samples_form_one_span() {
+        let samples = vec![sample("a", "x", 0), sample("a", "x", 10), sample("a", "x", 20)];
+        assert_eq!(ranges(&merge(&samples)), vec![("a", "x", 0, 30)]);
+    }
+
+    #[test]
+    fn window_change_ends_span_at_next_sample() {
+        let samples = vec![sample("a", "x", 0), sample("a", "x", 10), sample("a", "y", 19)];
+        assert_eq!(ranges(&merge(&samples)), vec![("a", "x", 0, 19), ("a", "y", 19, 29)]);
+    }
+
+    #[test]
+    fn gap_of_max_gap_is_continuous() {
+        let samples = vec![sample("a", "x", 0), sample("a", "x", MAX_GAP)];
+        assert_eq!(ranges(&merge(&samples)), vec![("a", "x", 0, 2 * MAX_GAP)]);
+    }
+
+    #[test]
+    fn gap_over_max_gap_splits() {
+        let samples = vec![sample("a", "x", 0), sample("a", "x", MAX_GAP + 1)];
+        assert_eq!(
+            ranges(&merge(&samples)),
+            vec![("a", "x", 0, SAMPLE_INTERVAL), ("a", "x", MAX_GAP + 1, MAX_GAP + 1 + SAMPLE_INTERVAL)],
+        );
+    }
+
+    #[test]
+    fn last_sample_lasts_the_usual_interval() {
+        // samples recorded every 30 seconds, the last one covers 30 seconds too
+        let samples = vec![sample("a", "x", 0), sample("a", "x", 30), sample("a", "x", 60), sample("a", "x", 200)];
+        assert_eq!(ranges(&merge_by_gap(&samples, 60, |_, _| true)), vec![("a", "x", 0, 90), ("a", "x", 200, 230)]);
+    }
+
+    #[test]
+    fn overlapping_hosts_are_merged_separately() {
+        let samples = vec![
+            sample("a", "x", 0),
+            sample("b", "y", 5),
+            sample("a", "x", 10),
+            sample("b", "y", 15),
+            sample("a", "x", 20),
+        ];
+        assert_eq!(ranges(&merge(&samples)), vec![("a", "x", 0, 30), ("b", "y", 5, 25)]);
+    }
+
+    #[test]
+    fn union_counts_time_of_hosts_once() {
+        let samples = vec![
+            sample("a", "x", 0),
+            sample("a", "x", 10),
+            sample("b", "x", 15),
+            sample("b", "y", 25),
+            sample("a", "y", 100),
+        ];
+        let spans = merge(&samples);
+        assert_eq!(ranges(&union(&spans, |_, _| true)), vec![("a", "x", 0, 35), ("a", "y", 100, 110)]);
+        assert_eq!(
+            ranges(&union(&spans, |a, b| a.class == b.class)),
+            vec![("a", "x", 0, 25), ("b", "y", 25, 35), ("a", "y", 100, 110)],
+        );
+        assert!(union(&[], |_, _| true).is_empty());
+    }
+
+    #[test]
+    fn clip_cuts_spans_to_range() {
+        let spans = merge(&[sample("a", "x", 0), sample("a", "x", 10), sample("a", "y", 40)]);
+        assert_eq!(ranges(&clip(&spans, 5, 45)), vec![("a", "x", 5, 20), ("a", "y", 40, 45)]);
+    }
+}
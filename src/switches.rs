@@ -0,0 +1,134 @@
+use crate::config::Config;
+use crate::db::Sample;
+use crate::spans::{self, Span, MAX_GAP};
+
+/// How fragmented the time of some samples is.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    /// Seconds of activity, idle time is not included.
+    pub active: i64,
+    /// Times of every switch to a different class without a break in between.
+    pub switches: Vec<i64>,
+    /// Uninterrupted time spent in a single class.
+    pub spans: Vec<Span>,
+    /// Uninterrupted time spent in classes that are neither distracting nor
+    /// used to chat, longest first.
+    pub deep_work: Vec<Span>,
+    /// Seconds it took to get back to another class after switching to chat.
+    pub returns: Vec<i64>,
+}
+
+impl Analysis {
+    pub fn switches_per_hour(&self) -> f64 {
+        if self.active == 0 {
+            return 0.0;
+        }
+        self.switches.len() as f64 * 3600.0 / self.active as f64
+    }
+
+    pub fn median_span(&self) -> i64 {
+        median(self.spans.iter().map(|s| s.seconds()).collect())
+    }
+
+    pub fn median_return(&self) -> i64 {
+        median(self.returns.clone())
+    }
+}
+
+pub fn median(mut values: Vec<i64>) -> i64 {
+    if values.is_empty() {
+        return 0;
+    }
+    values.sort();
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2
+    } else {
+        values[mid]
+    }
+}
+
+/// Analyzes the samples that are not idle, distracting and chat classes are
+/// taken from `focus.distracting` and `focus.chat` of the config.
+pub fn analyze(samples: &[Sample], config: &Config) -> Analysis {
+    let deep = |class: &str| !config.distracting(class) && !config.chat(class);
+    let active: Vec<Sample> = samples.iter().filter(|s| !s.idle).cloned().collect();
+    let mut analysis = Analysis {
+        spans: spans::merge_by(&active, |a, b| a.class == b.class),
+        ..Analysis::default()
+    };
+    analysis.active = analysis.spans.iter().map(|s| s.seconds()).sum();
+
+    analysis.deep_work = spans::merge_by(&active, |a, b| deep(&a.class) && deep(&b.class));
+    analysis.deep_work.retain(|s| deep(&s.class));
+    analysis.deep_work.sort_by_key(|s| std::cmp::Reverse(s.seconds()));
+
+    // samples of different machines don't switch between each other
+    let mut by_host: Vec<&Span> = analysis.spans.iter().collect();
+    by_host.sort_by(|a, b| (&a.host, a.start).cmp(&(&b.host, b.start)));
+    for (i, pair) in by_host.windows(2).enumerate() {
+        let (prev, next) = (pair[0], pair[1]);
+        if prev.host != next.host || next.start - prev.end > MAX_GAP {
+            continue;
+        }
+        analysis.switches.push(next.start);
+        if config.chat(&prev.class) || !config.chat(&next.class) {
+            continue;
+        }
+        // breaks taken after chatting count towards the time to return
+        let back = by_host[i + 1..].iter().take_while(|s| s.host == next.host).find(|s| !config.chat(&s.class));
+        if let Some(back) = back {
+            analysis.returns.push(back.start - next.start);
+        }
+    }
+    analysis.switches.sort();
+    analysis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sample;
+
+    fn config() -> Config {
+        Config::from_json(&json::parse(r#"{"focus": {"distracting": ["web"], "chat": ["slack"]}}"#).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn counts_switches_and_returns_from_chat() {
+        let samples = vec![
+            sample("a", "vim", 0),
+            sample("a", "vim", 10),
+            sample("a", "slack", 20),
+            sample("b", "web", 25),
+            sample("a", "vim", 30),
+            sample("a", "vim", 40),
+        ];
+        let analysis = analyze(&samples, &config());
+        assert_eq!(analysis.active, 60);
+        // the sample of the other machine is no switch
+        assert_eq!(analysis.switches, vec![20, 30]);
+        assert_eq!(analysis.returns, vec![10]);
+        assert_eq!(analysis.switches_per_hour(), 120.0);
+        let deep: Vec<(i64, i64)> = analysis.deep_work.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(deep, vec![(0, 20), (30, 50)]);
+    }
+
+    #[test]
+    fn breaks_and_idle_samples_are_no_switches() {
+        let mut samples = vec![sample("a", "vim", 0), sample("a", "web", 10 + MAX_GAP + 1), sample("a", "vim", 100)];
+        samples[2].idle = true;
+        let analysis = analyze(&samples, &config());
+        assert!(analysis.switches.is_empty());
+        assert_eq!(analysis.active, 20);
+        assert_eq!(analysis.deep_work.len(), 1);
+        assert_eq!(analysis.switches_per_hour(), 0.0);
+    }
+
+    #[test]
+    fn median_of_even_and_odd_counts() {
+        assert_eq!(median(vec![]), 0);
+        assert_eq!(median(vec![3, 1, 2]), 2);
+        assert_eq!(median(vec![4, 1]), 2);
+    }
+}
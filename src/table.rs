@@ -0,0 +1,82 @@
+/// A simple table that can be printed for the terminal, as Markdown or as CSV.
+#[derive(Debug, Default)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+pub fn fmt_duration(secs: i64) -> String {
+    format!("{}:{:0>2}:{:0>2}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+impl Table {
+    pub fn new(header: &[&str]) -> Table {
+        Table {
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> = self.header.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.chars().count());
+            }
+        }
+        widths
+    }
+
+    /// Columns are padded to the same width, all but the first are right aligned.
+    pub fn render_text(&self) -> String {
+        let widths = self.widths();
+        let line = |cells: &Vec<String>| {
+            let cells: Vec<String> = cells.iter().enumerate().map(|(i, c)| {
+                if i == 0 {
+                    format!("{:<w$}", c, w = widths[i])
+                } else {
+                    format!("{:>w$}", c, w = widths[i])
+                }
+            }).collect();
+            cells.join("  ").trim_end().to_string() + "\n"
+        };
+        let mut out = line(&self.header);
+        out += &widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("  ");
+        out += "\n";
+        for row in &self.rows {
+            out += &line(row);
+        }
+        out
+    }
+
+    pub fn render_csv(&self) -> String {
+        let escape = |c: &String| {
+            if c.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", c.replace('"', "\"\""))
+            } else {
+                c.clone()
+            }
+        };
+        let mut out = String::new();
+        for row in std::iter::once(&self.header).chain(self.rows.iter()) {
+            out += &row.iter().map(escape).collect::<Vec<_>>().join(",");
+            out += "\n";
+        }
+        out
+    }
+
+    pub fn render_markdown(&self) -> String {
+        let escape = |c: &String| c.replace('|', "\\|");
+        let line = |cells: &Vec<String>| format!("| {} |\n", cells.iter().map(escape).collect::<Vec<_>>().join(" | "));
+        let mut out = line(&self.header);
+        out += &format!("|{}|\n", self.header.iter().enumerate().map(|(i, _)| if i == 0 { " --- " } else { " ---: " }).collect::<Vec<_>>().join("|"));
+        for row in &self.rows {
+            out += &line(row);
+        }
+        out
+    }
+}
@@ -0,0 +1,173 @@
+/// A color as red, green and blue.
+pub type Rgb = (u8, u8, u8);
+
+/// Colors and font of the charts drawn by `gen_chart`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub background: Rgb,
+    /// Color of the hour lines and axes.
+    pub grid: Rgb,
+    pub text: Rgb,
+    /// Font family of all text, `None` uses hack and falls back to a sans
+    /// serif font if it isn't installed.
+    pub font: Option<String>,
+    /// Size of regular text in pixels, smaller text is scaled along.
+    pub font_size: u32,
+    /// Colors of the classes in the order of their time, classes beyond
+    /// them get random grays.
+    pub palette: Vec<Rgb>,
+    /// Color of the heatmap and calendar cells without activity.
+    pub heatmap_empty: Rgb,
+    /// Color of the heatmap and calendar cells with full activity.
+    pub heatmap: Rgb,
+    /// Color of the lines marking where the clocks were changed.
+    pub transition: Rgb,
+    /// Color of the context switch bars of `--switches`.
+    pub switches: Rgb,
+    /// Color of the deep work marks of `--switches`.
+    pub deep_work: Rgb,
+    /// Color of the bands of tagged ranges.
+    pub tags: Rgb,
+}
+
+/// The original colors of the classes.
+const PALETTE: [Rgb; 13] = [(255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 0), (255, 0, 255), (0, 255, 255), (255, 255, 255), (0, 0, 0), (85, 85, 85), (170, 170, 170), (128, 255, 0), (128, 0, 255), (255, 128, 0)];
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::gray()
+    }
+}
+
+impl Theme {
+    /// The original gray look.
+    pub fn gray() -> Theme {
+        Theme {
+            background: (128, 128, 128),
+            grid: (0, 0, 0),
+            text: (0, 0, 0),
+            font: None,
+            font_size: 16,
+            palette: PALETTE.to_vec(),
+            heatmap_empty: (64, 64, 64),
+            heatmap: (0, 200, 0),
+            transition: (200, 0, 0),
+            switches: (255, 140, 0),
+            deep_work: (0, 160, 0),
+            tags: (255, 255, 255),
+        }
+    }
+
+    pub fn light() -> Theme {
+        Theme {
+            background: (255, 255, 255),
+            grid: (190, 190, 190),
+            // white classes would vanish in the background
+            palette: PALETTE.iter().copied().filter(|c| *c != (255, 255, 255)).collect(),
+            heatmap_empty: (235, 235, 235),
+            heatmap: (0, 160, 0),
+            tags: (60, 60, 60),
+            ..Theme::gray()
+        }
+    }
+
+    pub fn dark() -> Theme {
+        Theme {
+            background: (30, 30, 30),
+            grid: (90, 90, 90),
+            text: (220, 220, 220),
+            palette: PALETTE.iter().copied().filter(|c| *c != (0, 0, 0)).collect(),
+            heatmap_empty: (50, 50, 50),
+            transition: (230, 60, 60),
+            tags: (220, 220, 220),
+            ..Theme::gray()
+        }
+    }
+
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "gray" => Some(Theme::gray()),
+            "light" => Some(Theme::light()),
+            "dark" => Some(Theme::dark()),
+            _ => None,
+        }
+    }
+
+    /// Reads the theme named by `theme`, gray by default, and overrides the
+    /// keys given in `obj`.
+    pub fn from_json(obj: &json::JsonValue) -> Result<Theme, String> {
+        let mut theme = match obj["theme"].as_str() {
+            None if obj["theme"].is_null() => Theme::default(),
+            Some(name) => Theme::named(name).ok_or(format!("theme {} is unknown, must be one of gray, light or dark", name))?,
+            None => return Err("theme must be a string".to_string()),
+        };
+        let colors = [
+            ("background", &mut theme.background),
+            ("grid", &mut theme.grid),
+            ("text", &mut theme.text),
+            ("heatmap_empty", &mut theme.heatmap_empty),
+            ("heatmap", &mut theme.heatmap),
+            ("transition", &mut theme.transition),
+            ("switches", &mut theme.switches),
+            ("deep_work", &mut theme.deep_work),
+            ("tags", &mut theme.tags),
+        ];
+        for (key, color) in colors {
+            if !obj[key].is_null() {
+                let s = obj[key].as_str().ok_or(format!("{} must be a color like #808080", key))?;
+                *color = parse_color(s).map_err(|e| format!("{}: {}", key, e))?;
+            }
+        }
+        if !obj["font"].is_null() {
+            theme.font = Some(obj["font"].as_str().ok_or("font must be a string")?.to_string());
+        }
+        if !obj["font_size"].is_null() {
+            theme.font_size = obj["font_size"].as_u32().filter(|s| *s >= 8).ok_or("font_size must be a number of pixels of at least 8")?;
+        }
+        Ok(theme)
+    }
+}
+
+/// Parses colors like `#1e1e1e`.
+pub fn parse_color(s: &str) -> Result<Rgb, String> {
+    let invalid = || format!("Invalid color {}, must be like #808080", s);
+    let hex = s.strip_prefix('#').filter(|h| h.len() == 6 && h.chars().all(|c| c.is_ascii_hexdigit())).ok_or_else(invalid)?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(parse_color("#1e1E1e"), Ok((30, 30, 30)));
+        for s in ["1e1e1e", "#1e1e1", "#1e1e1e1e", "#gggggg", "#+1e1e1", "#1e1é1", ""] {
+            assert!(parse_color(s).is_err(), "{} should be invalid", s);
+        }
+    }
+
+    #[test]
+    fn overrides_keys_of_theme() {
+        let theme = Theme::from_json(&json::parse(r##"{"theme": "dark", "grid": "#505050", "tags": "#ffff00"}"##).unwrap()).unwrap();
+        assert_eq!(theme, Theme { grid: (80, 80, 80), tags: (255, 255, 0), ..Theme::dark() });
+        assert_eq!(Theme::from_json(&json::JsonValue::Null), Ok(Theme::gray()));
+    }
+
+    #[test]
+    fn rejects_malformed_config() {
+        for config in [
+            r#"{"theme": "blue"}"#,
+            r#"{"theme": 1}"#,
+            r#"{"background": "gray"}"#,
+            r#"{"heatmap": 808080}"#,
+            r##"{"tags": "#12345"}"##,
+            r#"{"font": ["hack"]}"#,
+            r#"{"font_size": 4}"#,
+            r#"{"font_size": "16"}"#,
+        ] {
+            assert!(Theme::from_json(&json::parse(config).unwrap()).is_err(), "{} should be invalid", config);
+        }
+    }
+}
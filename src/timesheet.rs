@@ -0,0 +1,203 @@
+use regex::Regex;
+
+/// How billed time is rounded to whole increments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Up,
+    Nearest,
+    Down,
+}
+
+/// What the rounding is applied to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Per {
+    /// The time of a client and project per day.
+    Day,
+    /// Every uninterrupted block of work for a client and project.
+    Block,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rounding {
+    /// Seconds to round to.
+    pub increment: i64,
+    pub mode: Mode,
+    pub per: Per,
+}
+
+impl Default for Rounding {
+    fn default() -> Rounding {
+        Rounding { increment: 15 * 60, mode: Mode::Up, per: Per::Day }
+    }
+}
+
+impl Rounding {
+    /// Reads the keys given in `obj`, all others are taken from `base`.
+    pub fn from_json(obj: &json::JsonValue, base: &Rounding) -> Result<Rounding, String> {
+        let mut rounding = base.clone();
+        if !obj["increment"].is_null() {
+            let minutes = obj["increment"].as_u64().filter(|m| *m > 0).ok_or("increment must be a positive number of minutes")?;
+            rounding.increment = minutes as i64 * 60;
+        }
+        rounding.mode = match obj["mode"].as_str() {
+            None if obj["mode"].is_null() => rounding.mode,
+            Some("up") => Mode::Up,
+            Some("nearest") => Mode::Nearest,
+            Some("down") => Mode::Down,
+            m => return Err(format!("Unknown mode {:?}, must be one of up, nearest or down", m)),
+        };
+        rounding.per = match obj["per"].as_str() {
+            None if obj["per"].is_null() => rounding.per,
+            Some("day") => Per::Day,
+            Some("block") => Per::Block,
+            p => return Err(format!("Unknown per {:?}, must be one of day or block", p)),
+        };
+        Ok(rounding)
+    }
+
+    pub fn round(&self, secs: i64) -> i64 {
+        let whole = secs / self.increment * self.increment;
+        let rest = secs - whole;
+        match self.mode {
+            Mode::Up if rest > 0 => whole + self.increment,
+            Mode::Nearest if rest * 2 >= self.increment => whole + self.increment,
+            _ => whole,
+        }
+    }
+}
+
+/// Assigns windows whose class and title match the regexes to a project.
+#[derive(Debug)]
+pub struct Rule {
+    pub class: Option<Regex>,
+    pub title: Option<Regex>,
+    /// Empty if the client has no projects.
+    pub project: String,
+}
+
+impl Rule {
+    pub fn from_json(obj: &json::JsonValue) -> Result<Rule, String> {
+        let regex = |key: &str| -> Result<Option<Regex>, String> {
+            match obj[key].as_str() {
+                Some(r) => Regex::new(r).map(Some).map_err(|e| format!("Invalid {} regex {}: {}", key, r, e)),
+                None => Ok(None),
+            }
+        };
+        let rule = Rule {
+            class: regex("class")?,
+            title: regex("title")?,
+            project: obj["project"].as_str().unwrap_or_default().to_string(),
+        };
+        if rule.class.is_none() && rule.title.is_none() {
+            return Err("Rules need a class or title regex".to_string());
+        }
+        Ok(rule)
+    }
+
+    fn matches(&self, class: &str, title: &str) -> bool {
+        self.class.as_ref().is_none_or(|r| r.is_match(class)) && self.title.as_ref().is_none_or(|r| r.is_match(title))
+    }
+}
+
+#[derive(Debug)]
+pub struct Client {
+    pub name: String,
+    pub rounding: Rounding,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Default)]
+pub struct Timesheet {
+    /// Rounding of clients that don't set their own.
+    pub rounding: Rounding,
+    /// Clients in the order of the config, the first matching one is used.
+    pub clients: Vec<Client>,
+}
+
+impl Timesheet {
+    pub fn from_json(obj: &json::JsonValue) -> Result<Timesheet, String> {
+        let default = Rounding::from_json(&obj["rounding"], &Rounding::default()).map_err(|e| format!("rounding: {}", e))?;
+        let mut timesheet = Timesheet { rounding: default.clone(), clients: vec![] };
+        for (name, client) in obj["clients"].entries() {
+            let rounding = Rounding::from_json(&client["rounding"], &default).map_err(|e| format!("clients.{}.rounding: {}", name, e))?;
+            let mut rules = vec![];
+            for (i, rule) in client["rules"].members().enumerate() {
+                rules.push(Rule::from_json(rule).map_err(|e| format!("clients.{}.rules[{}]: {}", name, i, e))?);
+            }
+            timesheet.clients.push(Client { name: name.to_string(), rounding, rules });
+        }
+        Ok(timesheet)
+    }
+
+    /// The client and project a window is billed to.
+    pub fn assign(&self, class: &str, title: &str) -> Option<(&Client, &str)> {
+        self.clients.iter().find_map(|c| {
+            c.rules.iter().find(|r| r.matches(class, title)).map(|r| (c, r.project.as_str()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rounding(mode: Mode) -> Rounding {
+        Rounding { increment: 900, mode, per: Per::Day }
+    }
+
+    #[test]
+    fn round_up_at_boundaries() {
+        let r = rounding(Mode::Up);
+        assert_eq!(r.round(0), 0);
+        assert_eq!(r.round(1), 900);
+        assert_eq!(r.round(900), 900);
+        assert_eq!(r.round(901), 1800);
+    }
+
+    #[test]
+    fn round_nearest_at_boundaries() {
+        let r = rounding(Mode::Nearest);
+        assert_eq!(r.round(449), 0);
+        assert_eq!(r.round(450), 900);
+        assert_eq!(r.round(900), 900);
+        assert_eq!(r.round(1349), 900);
+        assert_eq!(r.round(1350), 1800);
+    }
+
+    #[test]
+    fn round_down_at_boundaries() {
+        let r = rounding(Mode::Down);
+        assert_eq!(r.round(899), 0);
+        assert_eq!(r.round(900), 900);
+        assert_eq!(r.round(1799), 900);
+    }
+
+    #[test]
+    fn rounding_inherits_unset_keys() {
+        let base = Rounding { increment: 600, mode: Mode::Down, per: Per::Block };
+        let r = Rounding::from_json(&json::parse(r#"{"mode": "nearest"}"#).unwrap(), &base).unwrap();
+        assert_eq!(r, Rounding { increment: 600, mode: Mode::Nearest, per: Per::Block });
+
+        assert!(Rounding::from_json(&json::parse(r#"{"increment": 0}"#).unwrap(), &base).is_err());
+        assert!(Rounding::from_json(&json::parse(r#"{"mode": "sideways"}"#).unwrap(), &base).is_err());
+    }
+
+    #[test]
+    fn first_matching_client_is_used() {
+        let config = json::parse(r#"{
+            "rounding": {"increment": 30},
+            "clients": {
+                "acme": {"rules": [{"title": "acme", "project": "web"}]},
+                "other": {"rounding": {"mode": "down"}, "rules": [{"class": "firefox"}]}
+            }
+        }"#).unwrap();
+        let timesheet = Timesheet::from_json(&config).unwrap();
+
+        let (client, project) = timesheet.assign("firefox", "acme dashboard").unwrap();
+        assert_eq!((client.name.as_str(), project), ("acme", "web"));
+        let (client, project) = timesheet.assign("firefox", "news").unwrap();
+        assert_eq!((client.name.as_str(), project), ("other", ""));
+        assert_eq!(client.rounding, Rounding { increment: 1800, mode: Mode::Down, per: Per::Day });
+        assert!(timesheet.assign("xterm", "news").is_none());
+    }
+}